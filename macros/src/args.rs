@@ -0,0 +1,472 @@
+use std::collections::HashMap;
+
+use proc_macro2::{Span, TokenStream};
+use quote::{quote, quote_spanned};
+use syn::{
+    ext::IdentExt, meta::ParseNestedMeta, parenthesized, punctuated::Punctuated, spanned::Spanned,
+    Attribute, Data, DeriveInput, Error, Expr, ExprLit, Fields, Ident, Lit, LitChar, LitStr, Meta,
+    Result, Token, Type,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Named,
+    Positional,
+    Rest,
+}
+
+struct Field {
+    ident: Ident,
+    ty: Type,
+    name: LitStr,
+    kind: Kind,
+    // The short form included
+    aliases: Vec<LitStr>,
+    default: Option<LitStr>,
+    required: bool,
+    docs: Vec<LitStr>,
+    value_parser: Option<Expr>,
+    duplicates: Option<Ident>,
+    delimiter: Option<LitChar>,
+    conflicts_with: Vec<LitStr>,
+    requires: Vec<LitStr>,
+}
+
+// `#[arg(at_least_one_of(...))]` or `#[arg(exactly_one_of(...))]` of a
+// struct
+struct Group {
+    kind: Ident,
+    names: Vec<LitStr>,
+}
+
+pub fn derive(input: DeriveInput) -> Result<TokenStream> {
+    let name = &input.ident;
+    if !input.generics.params.is_empty() {
+        return Err(Error::new_spanned(
+            &input.generics,
+            "`Args` can't be derived for generic structs",
+        ));
+    }
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(Error::new_spanned(
+                    name,
+                    "`Args` can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(Error::new_spanned(
+                name,
+                "`Args` can only be derived for structs with named fields",
+            ))
+        }
+    };
+
+    let mut groups = vec![];
+    let about = parse_about(&input.attrs, |meta| {
+        let kind = match meta.path.get_ident() {
+            Some(ident) if ident == "at_least_one_of" => Ident::new("AtLeastOneOf", ident.span()),
+            Some(ident) if ident == "exactly_one_of" => Ident::new("ExactlyOneOf", ident.span()),
+            _ => return Err(meta.error("unknown `arg` attribute for a struct")),
+        };
+        let content;
+        parenthesized!(content in meta.input);
+        let names = Punctuated::<LitStr, Token![,]>::parse_terminated(&content)?;
+        if names.len() < 2 {
+            return Err(meta.error("a group needs at least two arguments"));
+        }
+        groups.push(Group {
+            kind,
+            names: names.into_iter().collect(),
+        });
+        Ok(())
+    })?;
+    let fields = fields.iter().map(parse_field).collect::<Result<Vec<_>>>()?;
+    check_names(&fields)?;
+    check_constraints(&fields, &groups)?;
+
+    let field_defs = fields.iter().map(field_def);
+    let constraints = fields
+        .iter()
+        .flat_map(|field| {
+            let name = &field.name;
+            let conflicts = field.conflicts_with.iter().map(move |other| {
+                quote!(::tgbot_utils::cmd_arg::Constraint::ConflictsWith(#name, #other))
+            });
+            let requires = field.requires.iter().map(move |required| {
+                quote!(::tgbot_utils::cmd_arg::Constraint::Requires(#name, #required))
+            });
+            conflicts.chain(requires)
+        })
+        .chain(groups.iter().map(|Group { kind, names }| {
+            quote!(::tgbot_utils::cmd_arg::Constraint::#kind(&[#(#names),*]))
+        }));
+    let about = about.map(|about| {
+        quote! {
+            fn about() -> ::std::option::Option<&'static str> {
+                ::std::option::Option::Some(#about)
+            }
+        }
+    });
+    Ok(quote! {
+        impl ::tgbot_utils::cmd_arg::Args for #name {
+            const FIELDS: &'static [::tgbot_utils::cmd_arg::Field<Self>] = &[#(#field_defs),*];
+            const CONSTRAINTS: &'static [::tgbot_utils::cmd_arg::Constraint] =
+                &[#(#constraints),*];
+
+            #about
+        }
+
+        const _: () = ::tgbot_utils::cmd_arg::check_fields(
+            <#name as ::tgbot_utils::cmd_arg::Args>::FIELDS
+        );
+    })
+}
+
+// Any attribute other than `about` is left to `parse_other`. An empty about
+// leaves `about()` to its default.
+pub(crate) fn parse_about(
+    attrs: &[Attribute],
+    mut parse_other: impl FnMut(ParseNestedMeta) -> Result<()>,
+) -> Result<Option<String>> {
+    let mut about = None;
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("arg")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("about") {
+                about = Some(meta.value()?.parse::<LitStr>()?.value());
+                Ok(())
+            } else {
+                parse_other(meta)
+            }
+        })?;
+    }
+
+    let about = about.unwrap_or_else(|| join_docs(&docs(attrs)));
+    Ok((!about.is_empty()).then_some(about))
+}
+
+fn parse_field(field: &syn::Field) -> Result<Field> {
+    let ident = field.ident.clone().unwrap();
+    let mut parsed = Field {
+        name: LitStr::new(&ident.unraw().to_string(), ident.span()),
+        ident,
+        ty: field.ty.clone(),
+        kind: Kind::Named,
+        aliases: vec![],
+        default: None,
+        required: false,
+        docs: docs(&field.attrs),
+        value_parser: None,
+        duplicates: None,
+        delimiter: None,
+        conflicts_with: vec![],
+        requires: vec![],
+    };
+
+    for attr in field
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("arg"))
+    {
+        attr.parse_nested_meta(|meta| {
+            let path = &meta.path;
+            if path.is_ident("positional") || path.is_ident("rest") {
+                if parsed.kind != Kind::Named {
+                    return Err(meta.error("a field can't be both `positional` and `rest`"));
+                }
+                parsed.kind = match path.is_ident("rest") {
+                    true => Kind::Rest,
+                    false => Kind::Positional,
+                };
+            } else if path.is_ident("rename") {
+                parsed.name = meta.value()?.parse()?;
+            } else if path.is_ident("alias") {
+                parsed.aliases.push(meta.value()?.parse()?);
+            } else if path.is_ident("short") {
+                let short: LitChar = meta.value()?.parse()?;
+                if !short.value().is_ascii_alphanumeric() {
+                    return Err(Error::new(
+                        short.span(),
+                        "a short form must be an ASCII letter or digit",
+                    ));
+                }
+                parsed
+                    .aliases
+                    .push(LitStr::new(&short.value().to_string(), short.span()));
+            } else if path.is_ident("default") {
+                parsed.default = Some(meta.value()?.parse()?);
+            } else if path.is_ident("required") {
+                parsed.required = true;
+            } else if path.is_ident("help") {
+                parsed.docs = vec![meta.value()?.parse()?];
+            } else if path.is_ident("value_parser") {
+                parsed.value_parser = Some(meta.value()?.parse()?);
+            } else if path.is_ident("duplicates") {
+                let duplicates: LitStr = meta.value()?.parse()?;
+                let variant = match duplicates.value().as_str() {
+                    "error" => "Error",
+                    "last" => "Last",
+                    "collect" => "Collect",
+                    _ => {
+                        return Err(Error::new(
+                            duplicates.span(),
+                            "expected one of `error`, `last` or `collect`",
+                        ))
+                    }
+                };
+                parsed.duplicates = Some(Ident::new(variant, duplicates.span()));
+            } else if path.is_ident("delimiter") {
+                parsed.delimiter = Some(meta.value()?.parse()?);
+            } else if path.is_ident("conflicts_with") {
+                parsed.conflicts_with.push(meta.value()?.parse()?);
+            } else if path.is_ident("requires") {
+                parsed.requires.push(meta.value()?.parse()?);
+            } else {
+                return Err(meta.error("unknown `arg` attribute"));
+            }
+            Ok(())
+        })?;
+    }
+
+    if parsed.kind != Kind::Named {
+        let named_only = [
+            parsed.aliases.first().map(LitStr::span),
+            parsed.duplicates.as_ref().map(Ident::span),
+            parsed.delimiter.as_ref().map(LitChar::span),
+        ];
+        if let Some(span) = named_only.into_iter().flatten().next() {
+            return Err(Error::new(
+                span,
+                "only named arguments can have aliases, short forms, a duplicates policy or \
+                 a delimiter",
+            ));
+        }
+    }
+    Ok(parsed)
+}
+
+// Names of named arguments are matched against whole tokens, so they can't
+// contain anything the tokenizer or `Arg::parse` treats specially
+fn check_names(fields: &[Field]) -> Result<()> {
+    let mut seen: HashMap<String, Span> = HashMap::new();
+
+    for field in fields.iter().filter(|field| field.kind == Kind::Named) {
+        for name in [&field.name].into_iter().chain(&field.aliases) {
+            let value = name.value();
+            let is_valid = !value.is_empty()
+                && !value.starts_with(['+', '-'])
+                && !value.contains(|ch: char| {
+                    ch.is_whitespace() || matches!(ch, '=' | '"' | '\'' | '\\')
+                });
+            if !is_valid {
+                return Err(Error::new(
+                    name.span(),
+                    format!("`{value}` can't be the name of an argument"),
+                ));
+            }
+            if seen.insert(value.clone(), name.span()).is_some() {
+                return Err(Error::new(
+                    name.span(),
+                    format!("the argument name `{value}` is used more than once"),
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Constraints refer to fields by their names, aliases aren't accepted
+fn check_constraints(fields: &[Field], groups: &[Group]) -> Result<()> {
+    let field_names = fields
+        .iter()
+        .map(|field| field.name.value())
+        .collect::<Vec<_>>();
+    let check_name = |name: &LitStr| match field_names.contains(&name.value()) {
+        true => Ok(()),
+        false => Err(Error::new(
+            name.span(),
+            format!("there's no argument named `{}`", name.value()),
+        )),
+    };
+
+    for field in fields {
+        for name in field.conflicts_with.iter().chain(&field.requires) {
+            check_name(name)?;
+            if name.value() == field.name.value() {
+                return Err(Error::new(
+                    name.span(),
+                    "an argument can't conflict with or require itself",
+                ));
+            }
+        }
+    }
+    for name in groups.iter().flat_map(|group| &group.names) {
+        check_name(name)?;
+    }
+
+    Ok(())
+}
+
+fn field_def(field: &Field) -> TokenStream {
+    let Field {
+        ident,
+        ty,
+        name,
+        aliases,
+        required,
+        docs,
+        ..
+    } = field;
+    let default = match &field.default {
+        Some(default) => quote!(Some(#default)),
+        None => quote!(None),
+    };
+    let delimiter = match &field.delimiter {
+        Some(delimiter) => quote!(Some(#delimiter)),
+        None => quote!(None),
+    };
+    let duplicates = |form: TokenStream| match &field.duplicates {
+        Some(duplicates) => quote!(::tgbot_utils::cmd_arg::Duplicates::#duplicates),
+        None => quote!(::tgbot_utils::cmd_arg::Duplicates::default_for(#form)),
+    };
+    let variant = match field.kind {
+        Kind::Named => quote!(Named),
+        Kind::Positional => quote!(Positional),
+        Kind::Rest => quote!(Rest),
+    };
+
+    let kind = match (field.kind, &field.value_parser) {
+        (Kind::Named, None) => {
+            let form = quote_spanned!(ty.span()=> <#ty as ::tgbot_utils::cmd_arg::NamedArg>::FORM);
+            let duplicates = duplicates(form.clone());
+            quote_spanned! {ty.span()=>
+                ::tgbot_utils::cmd_arg::FieldKind::Named {
+                    form: #form,
+                    required: #required,
+                    duplicates: #duplicates,
+                    delimiter: #delimiter,
+                    apply: |args, value| {
+                        ::tgbot_utils::cmd_arg::NamedArg::apply(&mut args.#ident, value)
+                    },
+                    clear: |args| ::tgbot_utils::cmd_arg::NamedArg::clear(&mut args.#ident),
+                }
+            }
+        }
+        (Kind::Named, Some(parser)) => {
+            let form = quote!(::tgbot_utils::cmd_arg::NamedForm::Value);
+            let duplicates = duplicates(form.clone());
+            let set = set_parsed(field, parser);
+            quote! {
+                ::tgbot_utils::cmd_arg::FieldKind::Named {
+                    form: #form,
+                    required: #required,
+                    duplicates: #duplicates,
+                    delimiter: #delimiter,
+                    clear: |_| {},
+                    apply: |args, value| match value {
+                        Some(::tgbot_utils::cmd_arg::ArgValue::KV(value)) => {
+                            let value: &str = value;
+                            #set;
+                            Ok(())
+                        }
+                        _ => Err(::tgbot_utils::cmd_arg::ApplyError::IllFormed),
+                    },
+                }
+            }
+        }
+        (_, None) => quote_spanned! {ty.span()=>
+            ::tgbot_utils::cmd_arg::FieldKind::#variant {
+                required: <#ty as ::tgbot_utils::cmd_arg::PositionalArg>::REQUIRED || #required,
+                apply: |args, value| {
+                    ::tgbot_utils::cmd_arg::PositionalArg::apply(&mut args.#ident, value)
+                },
+            }
+        },
+        (_, Some(parser)) => {
+            let required = *required || !is_option(ty);
+            let set = set_parsed(field, parser);
+            quote! {
+                ::tgbot_utils::cmd_arg::FieldKind::#variant {
+                    required: #required,
+                    apply: |args, value| {
+                        #set;
+                        Ok(())
+                    },
+                }
+            }
+        }
+    };
+
+    quote! {
+        ::tgbot_utils::cmd_arg::Field {
+            name: #name,
+            aliases: &[#(#aliases),*],
+            kind: #kind,
+            docs: &[#(#docs),*],
+            default: #default,
+        }
+    }
+}
+
+// A value parser is a `fn(&str) -> Result<T, &'static str>`, the error
+// describing what was expected. `Option<T>` fields are set to `Some`.
+fn set_parsed(field: &Field, parser: &Expr) -> TokenStream {
+    let ident = &field.ident;
+    let parsed = quote_spanned! {parser.span()=>
+        (#parser)(value).map_err(::tgbot_utils::cmd_arg::ApplyError::InvalidValue)?
+    };
+    match is_option(&field.ty) {
+        true => quote!(args.#ident = Some(#parsed)),
+        false => quote!(args.#ident = #parsed),
+    }
+}
+
+fn is_option(ty: &Type) -> bool {
+    match ty {
+        Type::Path(path) => path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "Option"),
+        _ => false,
+    }
+}
+
+pub(crate) fn docs(attrs: &[Attribute]) -> Vec<LitStr> {
+    attrs
+        .iter()
+        .filter_map(|attr| match &attr.meta {
+            Meta::NameValue(meta) if meta.path.is_ident("doc") => match &meta.value {
+                Expr::Lit(ExprLit {
+                    lit: Lit::Str(doc), ..
+                }) => Some(doc.clone()),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect()
+}
+
+// Lines are joined with spaces, blank ones separating paragraphs
+fn join_docs(lines: &[LitStr]) -> String {
+    let mut text = String::new();
+    for line in lines {
+        let line = line.value();
+        let line = line.trim();
+        if line.is_empty() {
+            if !text.is_empty() && !text.ends_with('\n') {
+                text.push('\n');
+            }
+        } else {
+            if !text.is_empty() && !text.ends_with('\n') {
+                text.push(' ');
+            }
+            text.push_str(line);
+        }
+    }
+    text
+}
@@ -0,0 +1,188 @@
+use std::collections::HashMap;
+
+use proc_macro2::{Span, TokenStream};
+use quote::{quote, quote_spanned};
+use syn::{
+    spanned::Spanned, Data, DeriveInput, Error, Fields, Ident, LitStr, Result, Type, Variant,
+};
+
+use crate::args::{docs, parse_about};
+
+struct Subcommand {
+    ident: Ident,
+    // `None` for unit variants
+    ty: Option<Type>,
+    name: LitStr,
+    aliases: Vec<LitStr>,
+    docs: Vec<LitStr>,
+}
+
+pub fn derive(input: DeriveInput) -> Result<TokenStream> {
+    let name = &input.ident;
+    if !input.generics.params.is_empty() {
+        return Err(Error::new_spanned(
+            &input.generics,
+            "`Subcommands` can't be derived for generic enums",
+        ));
+    }
+    let Data::Enum(data) = &input.data else {
+        return Err(Error::new_spanned(
+            name,
+            "`Subcommands` can only be derived for enums",
+        ));
+    };
+    if data.variants.is_empty() {
+        return Err(Error::new_spanned(
+            name,
+            "`Subcommands` can't be derived for an enum without variants",
+        ));
+    }
+
+    let about = parse_about(&input.attrs, |meta| {
+        Err(meta.error("unknown `arg` attribute for an enum"))
+    })?;
+    let subcommands = data
+        .variants
+        .iter()
+        .map(parse_variant)
+        .collect::<Result<Vec<_>>>()?;
+    check_names(&subcommands)?;
+
+    let subcommand_defs = subcommands.iter().map(subcommand_def);
+    let about = about.map(|about| {
+        quote! {
+            fn about() -> ::std::option::Option<&'static str> {
+                ::std::option::Option::Some(#about)
+            }
+        }
+    });
+    Ok(quote! {
+        impl ::tgbot_utils::cmd_arg::Subcommands for #name {
+            const SUBCOMMANDS: &'static [::tgbot_utils::cmd_arg::Subcommand<Self>] =
+                &[#(#subcommand_defs),*];
+
+            #about
+        }
+    })
+}
+
+fn parse_variant(variant: &Variant) -> Result<Subcommand> {
+    let ty = match &variant.fields {
+        Fields::Unit => None,
+        Fields::Unnamed(fields) if fields.unnamed.len() == 1 => Some(fields.unnamed[0].ty.clone()),
+        _ => {
+            return Err(Error::new_spanned(
+                &variant.fields,
+                "subcommand variants must be unit or hold a single `Args` struct",
+            ))
+        }
+    };
+
+    let ident = variant.ident.clone();
+    let mut parsed = Subcommand {
+        name: LitStr::new(&snake_case(&ident.to_string()), ident.span()),
+        ident,
+        ty,
+        aliases: vec![],
+        docs: docs(&variant.attrs),
+    };
+
+    for attr in variant
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("arg"))
+    {
+        attr.parse_nested_meta(|meta| {
+            let path = &meta.path;
+            if path.is_ident("rename") {
+                parsed.name = meta.value()?.parse()?;
+            } else if path.is_ident("alias") {
+                parsed.aliases.push(meta.value()?.parse()?);
+            } else if path.is_ident("help") {
+                parsed.docs = vec![meta.value()?.parse()?];
+            } else {
+                return Err(meta.error("unknown `arg` attribute for a subcommand"));
+            }
+            Ok(())
+        })?;
+    }
+
+    Ok(parsed)
+}
+
+// A subcommand name is a whole token, so it can't be empty or contain
+// whitespace or quotes
+fn check_names(subcommands: &[Subcommand]) -> Result<()> {
+    let mut seen: HashMap<String, Span> = HashMap::new();
+
+    for subcommand in subcommands {
+        for name in [&subcommand.name].into_iter().chain(&subcommand.aliases) {
+            let value = name.value();
+            let is_valid = !value.is_empty()
+                && !value
+                    .contains(|ch: char| ch.is_whitespace() || matches!(ch, '"' | '\'' | '\\'));
+            if !is_valid {
+                return Err(Error::new(
+                    name.span(),
+                    format!("`{value}` can't be the name of a subcommand"),
+                ));
+            }
+            if seen.insert(value.clone(), name.span()).is_some() {
+                return Err(Error::new(
+                    name.span(),
+                    format!("the subcommand name `{value}` is used more than once"),
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn subcommand_def(subcommand: &Subcommand) -> TokenStream {
+    let Subcommand {
+        ident,
+        name,
+        aliases,
+        docs,
+        ..
+    } = subcommand;
+    let (args, parse) = match &subcommand.ty {
+        Some(ty) => (
+            quote_spanned!(ty.span()=> <#ty as ::tgbot_utils::cmd_arg::Args>),
+            quote!(|input| ::tgbot_utils::cmd_arg::Args::parse(input).map(Self::#ident)),
+        ),
+        None => (
+            quote!(<() as ::tgbot_utils::cmd_arg::Args>),
+            quote!(|input| <() as ::tgbot_utils::cmd_arg::Args>::parse(input).map(|()| Self::#ident)),
+        ),
+    };
+
+    quote! {
+        ::tgbot_utils::cmd_arg::Subcommand {
+            name: #name,
+            aliases: &[#(#aliases),*],
+            docs: &[#(#docs),*],
+            about: #args::about,
+            usage: #args::usage,
+            help: #args::help,
+            parse: #parse,
+        }
+    }
+}
+
+// `ListAll` is `list_all`
+fn snake_case(ident: &str) -> String {
+    let mut name = String::new();
+    for (i, ch) in ident.chars().enumerate() {
+        if ch.is_uppercase() {
+            if i > 0 {
+                name.push('_');
+            }
+            name.extend(ch.to_lowercase());
+        } else {
+            name.push(ch);
+        }
+    }
+    name
+}
@@ -0,0 +1,6 @@
+CREATE TABLE IF NOT EXISTS "telegram_message_text" (
+    "key"       TEXT NOT NULL,
+    "text_json" TEXT NOT NULL,
+
+    UNIQUE("key") ON CONFLICT REPLACE
+);
@@ -1,12 +1,94 @@
-use std::fmt;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    time::Duration,
+};
+
+use teloxide::types::{ChatId, UserId};
+use url::Url;
+
+use crate::text::{mtb, parse_tg_link, MessageLink, MessageText, TgLink};
+
+mod subcommand;
+
+pub use subcommand::{Subcommand, Subcommands};
+pub use tgbot_utils_macros::{Args, Subcommands};
 
 #[derive(thiserror::Error, Debug)]
 pub enum CmdArgError {
-    #[error("unrecognized or ill-formed argument: {0}")]
-    UnrecognizedOrIllFormed(String),
+    #[error(
+        "unknown argument `{arg}`{}",
+        suggestion.map(|name| format!(", did you mean `{name}`?")).unwrap_or_default()
+    )]
+    UnknownArgument {
+        arg: String,
+        // The closest declared name, if any is close enough
+        suggestion: Option<&'static str>,
+    },
+
+    // E.g. `+name=value`, which could mean either
+    #[error("argument `{0}` can't have both a sign and a value")]
+    SignedValue(String),
+
+    #[error("ill-formed argument `{arg}`, expected `{expected}`")]
+    IllFormedArgument {
+        name: &'static str,
+        arg: String,
+        expected: String,
+    },
+
+    // Positions are in chars
+    #[error("unterminated quote at position {0}")]
+    UnterminatedQuote(usize),
+
+    #[error("nothing to escape at position {0}")]
+    DanglingEscape(usize),
+
+    #[error("missing subcommand, expected one of {}", list_names(valid))]
+    MissingSubcommand { valid: Vec<&'static str> },
+
+    #[error(
+        "unknown subcommand `{name}` (expected one of {}){}",
+        list_names(valid),
+        suggestion.map(|name| format!(", did you mean `{name}`?")).unwrap_or_default()
+    )]
+    UnknownSubcommand {
+        name: String,
+        suggestion: Option<&'static str>,
+        valid: Vec<&'static str>,
+    },
+
+    #[error("argument `{0}` is given more than once")]
+    DuplicateArgument(String),
+
+    #[error("missing required argument `{0}`")]
+    MissingArgument(&'static str),
+
+    #[error("arguments `{name}` and `{other}` can't be given together")]
+    ConflictingArguments {
+        name: &'static str,
+        other: &'static str,
+    },
+
+    #[error("argument `{name}` requires `{required}`")]
+    MissingRequirement {
+        name: &'static str,
+        required: &'static str,
+    },
+
+    #[error("one of {} is required", list_names(.0))]
+    MissingOneOf(Vec<&'static str>),
+
+    // The ones given
+    #[error("only one of {} can be given", list_names(.0))]
+    MoreThanOneOf(Vec<&'static str>),
 
-    #[error("+/-option with =value is not supported yet")]
-    UnsupportedBoolKVOption,
+    #[error("invalid value for argument `{name}`: {value}, expected {expected}")]
+    InvalidValue {
+        name: &'static str,
+        value: String,
+        expected: &'static str,
+    },
 
     #[error("{0}")]
     Download(#[from] teloxide::errors::DownloadError),
@@ -14,50 +96,753 @@ pub enum CmdArgError {
 
 type Result<T> = std::result::Result<T, CmdArgError>;
 
-pub trait Args: Default {
-    fn help() -> &'static str;
+// A parsing error shown along with how the command is used
+#[derive(thiserror::Error, Debug)]
+#[error("{error}\nUsage: {usage}")]
+pub struct UsageError {
+    pub error: CmdArgError,
+    pub usage: String,
+}
+
+// E.g. "`get`, `set`, `reset`"
+fn list_names(names: &[&str]) -> String {
+    names
+        .iter()
+        .map(|name| format!("`{name}`"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` doesn't implement `Args`",
+    note = "use `#[derive(Args)]` on a struct with named fields"
+)]
+pub trait Args: Default + 'static {
+    // Generated by `#[derive(Args)]`, in declaration order. Hand-written
+    // impls overriding `parse` may leave it empty.
+    const FIELDS: &'static [Field<Self>] = &[];
+
+    // Checked once all the fields are parsed, fields with only a default
+    // value and flags or switches turned off don't count as given
+    const CONSTRAINTS: &'static [Constraint] = &[];
+
+    // The doc comment or `#[arg(about = "...")]` of the struct
+    fn about() -> Option<&'static str> {
+        None
+    }
+
+    // E.g. `<target> [silent] [duration=<value>] [reason...]`
+    fn usage() -> String {
+        Self::FIELDS
+            .iter()
+            .map(Field::syntax)
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn help() -> MessageText<'static> {
+        let mut builder = mtb().plain(Self::about().unwrap_or_default());
+        if !Self::FIELDS.is_empty() {
+            let separator = if Self::about().is_some() { "\n\n" } else { "" };
+            builder = builder
+                .plain(format!("{separator}Usage: "))
+                .code(Self::usage())
+                .plain("\n");
+        }
+
+        for field in Self::FIELDS {
+            builder = builder
+                .plain("\n• ")
+                .bold(field.name)
+                .plain(" ")
+                .code(field.syntax());
+            for alias in field.aliases {
+                builder = builder.plain(", ").code(*alias);
+            }
+
+            let description = field.description();
+            if !description.is_empty() || field.default.is_some() {
+                builder = builder.plain("\n").plain(&description);
+            }
+            if let Some(default) = field.default {
+                let separator = if description.is_empty() { "" } else { " " };
+                builder = builder
+                    .plain(format!("{separator}(default: "))
+                    .code(default)
+                    .plain(")");
+            }
+        }
+
+        builder.build()
+    }
+
+    // A token matching the name of a named field is applied to it, any other
+    // one fills the next positional field. Once the next positional field is a
+    // rest one, the first token which isn't a named argument, or is quoted,
+    // starts it, the remaining input being taken verbatim, without tokenizing
+    // it.
+    // Defaults of the fields not given are then applied as if given by the
+    // user.
+    fn parse(input: impl AsRef<str>) -> Result<Self> {
+        let input = input.as_ref();
+        let mut args = Self::default();
+        let mut given = vec![false; Self::FIELDS.len()];
+        // Flags and switches last given as off, e.g. `-ban` or `ban=no`
+        let mut off = vec![false; Self::FIELDS.len()];
+        // Names of the named fields given, with the key for map fields
+        let mut seen = HashSet::new();
+        let mut positionals = Self::FIELDS
+            .iter()
+            .enumerate()
+            .filter(|(_, field)| !matches!(field.kind, FieldKind::Named { .. }))
+            .peekable();
+        let mut tokens = Tokenizer::new(input);
+
+        loop {
+            let rest = positionals.next_if(|(_, field)| {
+                matches!(field.kind, FieldKind::Rest { .. })
+                    && !is_named_next(Self::FIELDS, tokens.clone())
+            });
+            if let Some((index, field)) = rest {
+                if let Some(start) = tokens.next_start() {
+                    let arg = Arg {
+                        name: field.name.into(),
+                        value: Some(ArgValue::KV(input[start..].trim_end().into())),
+                    };
+                    field.apply(&mut args, arg)?;
+                    given[index] = true;
+                }
+                break;
+            }
+
+            let Some(token) = tokens.next().transpose()? else {
+                break;
+            };
+            let mut arg = Arg::parse(&token)?;
+            if let Some((index, field)) = find_named(Self::FIELDS, &mut arg) {
+                let FieldKind::Named {
+                    form,
+                    duplicates,
+                    clear,
+                    ..
+                } = field.kind
+                else {
+                    unreachable!()
+                };
+                let key = match &arg.value {
+                    Some(ArgValue::Entry { key, .. }) => format!("{}.{key}", field.name),
+                    _ => field.name.into(),
+                };
+                if !seen.insert(key.clone()) {
+                    match duplicates {
+                        Duplicates::Error => return Err(CmdArgError::DuplicateArgument(key)),
+                        Duplicates::Last => clear(&mut args),
+                        Duplicates::Collect => {}
+                    }
+                }
+
+                off[index] = matches!(form, NamedForm::Flag | NamedForm::Switch) && arg.is_off();
+                field.apply(&mut args, arg)?;
+                given[index] = true;
+                continue;
+            }
+
+            // A mistyped name is only reported if it can't be a positional value,
+            // with no positional field left or in the `name=value`, `+name` or
+            // `-name` form. A quoted token is never taken for a name.
+            let suggestion = suggest(Self::FIELDS, &arg.name).filter(|_| !token.literal_start);
+            let slot = positionals.next_if(|_| suggestion.is_none() || arg.value.is_none());
+            let Some((index, field)) = slot else {
+                return Err(CmdArgError::UnknownArgument {
+                    suggestion,
+                    arg: token.value,
+                });
+            };
+            let arg = Arg {
+                name: field.name.into(),
+                value: Some(ArgValue::KV(token.value)),
+            };
+            field.apply(&mut args, arg)?;
+            given[index] = true;
+        }
+
+        for (field, _) in Self::FIELDS
+            .iter()
+            .zip(&given)
+            .filter(|(_, given)| !**given)
+        {
+            if let Some(default) = field.default {
+                let arg = Arg {
+                    name: field.name.into(),
+                    value: Some(ArgValue::KV(default.into())),
+                };
+                field.apply(&mut args, arg)?;
+            }
+        }
+
+        let missing = Self::FIELDS
+            .iter()
+            .zip(&given)
+            .find(|(field, given)| field.is_required() && !**given);
+        if let Some((field, _)) = missing {
+            return Err(CmdArgError::MissingArgument(field.name));
+        }
 
-    fn parse(input: impl AsRef<str>) -> Result<Self>;
+        check_constraints(Self::CONSTRAINTS, |name| {
+            Self::FIELDS
+                .iter()
+                .zip(given.iter().zip(&off))
+                .any(|(field, (given, off))| *given && !off && field.name == name)
+        })?;
+        Ok(args)
+    }
+
+    // For replying to the user, who should see how the command is used
+    fn parse_or_usage(input: impl AsRef<str>) -> std::result::Result<Self, UsageError> {
+        Self::parse(input).map_err(|error| UsageError {
+            error,
+            usage: Self::usage(),
+        })
+    }
 
+    // For hand-written `parse` implementations, every argument is passed to
+    // `predicate`, which returns whether it was taken. Fields and defaults
+    // aren't involved, but the constraints are checked, an argument counting
+    // as given unless last turned off, e.g. by `-name` or `name=no`.
     fn parse_inner(
         input: impl AsRef<str>,
         predicate: impl Fn(&mut Self, &str, Option<&ArgValue>) -> bool,
-    ) -> Result<Self>
-    where
-        Self: Sized,
-    {
+    ) -> Result<Self> {
         let mut args = Self::default();
+        // Whether each argument taken, by field name if any, was last on
+        let mut on = HashMap::new();
 
-        let parsed_args: Result<Vec<_>> =
-            input.as_ref().split_whitespace().map(Arg::parse).collect();
-
-        for arg in parsed_args?.into_iter() {
+        for token in tokenize(input.as_ref())? {
+            let arg = Arg::parse(&token)?;
             if !predicate(&mut args, &arg.name, arg.value.as_ref()) {
-                return Err(CmdArgError::UnrecognizedOrIllFormed(arg.to_string()));
+                return Err(CmdArgError::UnknownArgument {
+                    suggestion: suggest(Self::FIELDS, &arg.name),
+                    arg: token.value,
+                });
             }
+
+            let name = Self::FIELDS
+                .iter()
+                .find(|field| field.is_named(&arg.name))
+                .map_or(arg.name.as_str(), |field| field.name);
+            let off = match &arg.value {
+                Some(ArgValue::KV(value)) if parse_bool(value).is_none() => false,
+                _ => arg.is_off(),
+            };
+            on.insert(name.to_owned(), !off);
         }
 
+        check_constraints(Self::CONSTRAINTS, |name| on.get(name) == Some(&true))?;
         Ok(args)
     }
 }
 
+pub struct Field<A> {
+    pub name: &'static str,
+    // Other names of a named field, its short form included
+    pub aliases: &'static [&'static str],
+    pub kind: FieldKind<A>,
+    // Lines of the field's doc comment
+    pub docs: &'static [&'static str],
+    // Applied like a value given by the user, making a positional field
+    // optional
+    pub default: Option<&'static str>,
+}
+
+pub enum FieldKind<A> {
+    Named {
+        form: NamedForm,
+        required: bool,
+        // How giving the field again, or the same key of a map field, is
+        // handled
+        duplicates: Duplicates,
+        // Splits each value of a list field into several
+        delimiter: Option<char>,
+        apply: fn(&mut A, Option<&ArgValue>) -> ApplyResult,
+        // Called before a list field given again replaces its values
+        clear: fn(&mut A),
+    },
+    Positional {
+        required: bool,
+        apply: fn(&mut A, &str) -> ApplyResult,
+    },
+    // The rest of the input, spaces included
+    Rest {
+        required: bool,
+        apply: fn(&mut A, &str) -> ApplyResult,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NamedForm {
+    Flag,   // `name`
+    Switch, // `+name`, `-name`
+    Value,  // `name=value`
+    List,   // `name=value`, repeated
+    Map,    // `name.key=value`
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Duplicates {
+    Error,
+    // The last value replaces the previous ones
+    Last,
+    // All values are kept, only for list fields
+    Collect,
+}
+
+impl Duplicates {
+    // The last value has always won for fields taking a single one, while
+    // giving the same key of a map twice is most likely a mistake
+    pub const fn default_for(form: NamedForm) -> Self {
+        match form {
+            NamedForm::List => Self::Collect,
+            NamedForm::Map => Self::Error,
+            NamedForm::Flag | NamedForm::Switch | NamedForm::Value => Self::Last,
+        }
+    }
+}
+
+// Relations between fields, referred to by their names
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Constraint {
+    // Both can't be given, regardless of the order
+    ConflictsWith(&'static str, &'static str),
+    // The first can only be given with the second
+    Requires(&'static str, &'static str),
+    AtLeastOneOf(&'static [&'static str]),
+    ExactlyOneOf(&'static [&'static str]),
+}
+
+pub enum ApplyError {
+    // E.g. `+name` given for a field that takes a value
+    IllFormed,
+    // The value couldn't be parsed, with a description of what was expected
+    InvalidValue(&'static str),
+}
+
+pub type ApplyResult = std::result::Result<(), ApplyError>;
+
+impl<A> Field<A> {
+    fn is_required(&self) -> bool {
+        match self.kind {
+            FieldKind::Named { required, .. }
+            | FieldKind::Positional { required, .. }
+            | FieldKind::Rest { required, .. } => required && self.default.is_none(),
+        }
+    }
+
+    fn is_named(&self, name: &str) -> bool {
+        self.name == name || self.aliases.contains(&name)
+    }
+
+    fn apply(&self, args: &mut A, arg: Arg) -> Result<()> {
+        if let (
+            FieldKind::Named {
+                delimiter: Some(delimiter),
+                ..
+            },
+            Some(ArgValue::KV(value)),
+        ) = (&self.kind, &arg.value)
+        {
+            for part in value.split(*delimiter).filter(|part| !part.is_empty()) {
+                let arg = Arg {
+                    name: arg.name.clone(),
+                    value: Some(ArgValue::KV(part.into())),
+                };
+                self.apply_one(args, arg)?;
+            }
+            return Ok(());
+        }
+
+        self.apply_one(args, arg)
+    }
+
+    // Positional and rest fields take the value of `name=value`
+    fn apply_one(&self, args: &mut A, arg: Arg) -> Result<()> {
+        let result = match (&self.kind, &arg.value) {
+            (FieldKind::Named { apply, .. }, value) => apply(args, value.as_ref()),
+            (
+                FieldKind::Positional { apply, .. } | FieldKind::Rest { apply, .. },
+                Some(ArgValue::KV(value)),
+            ) => apply(args, value),
+            _ => Err(ApplyError::IllFormed),
+        };
+
+        match result {
+            Ok(()) => Ok(()),
+            Err(ApplyError::IllFormed) => Err(CmdArgError::IllFormedArgument {
+                name: self.name,
+                arg: arg.to_string(),
+                expected: self.form(),
+            }),
+            Err(ApplyError::InvalidValue(expected)) => {
+                let value = match arg.value {
+                    Some(ArgValue::KV(value) | ArgValue::Entry { value, .. }) => value,
+                    _ => arg.to_string(),
+                };
+                Err(CmdArgError::InvalidValue {
+                    name: self.name,
+                    value,
+                    expected,
+                })
+            }
+        }
+    }
+
+    // How the field is given, e.g. `name=<value>`
+    fn form(&self) -> String {
+        let name = self.name;
+        match self.kind {
+            FieldKind::Named {
+                form, delimiter, ..
+            } => match (form, delimiter) {
+                (NamedForm::Flag, _) => format!("{name}[=yes|no]"),
+                (NamedForm::Switch, _) => format!("+{name}|-{name}|{name}=yes|no|default"),
+                (NamedForm::Value, _) => format!("{name}=<value>"),
+                (NamedForm::List, Some(delimiter)) => format!("{name}=<value>{delimiter}..."),
+                (NamedForm::List, None) => format!("{name}=<value>..."),
+                (NamedForm::Map, _) => format!("{name}.<key>=<value>"),
+            },
+            FieldKind::Positional { .. } => format!("<{name}>"),
+            FieldKind::Rest { .. } => format!("<{name}...>"),
+        }
+    }
+
+    fn syntax(&self) -> String {
+        let name = self.name;
+        match self.kind {
+            FieldKind::Named { .. } if self.is_required() => self.form(),
+            FieldKind::Named { .. } => format!("[{}]", self.form()),
+            FieldKind::Positional { .. } if self.is_required() => format!("<{name}>"),
+            FieldKind::Positional { .. } => format!("[{name}]"),
+            FieldKind::Rest { .. } if self.is_required() => format!("<{name}...>"),
+            FieldKind::Rest { .. } => format!("[{name}...]"),
+        }
+    }
+
+    fn description(&self) -> String {
+        self.docs
+            .iter()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+// Also turns `name.key=value` given for a map field into an entry
+fn find_named<'f, A>(fields: &'f [Field<A>], arg: &mut Arg) -> Option<(usize, &'f Field<A>)> {
+    let find = |name: &str, map_only: bool| {
+        fields
+            .iter()
+            .enumerate()
+            .find(|(_, field)| match field.kind {
+                FieldKind::Named { form, .. } => {
+                    (!map_only || form == NamedForm::Map) && field.is_named(name)
+                }
+                _ => false,
+            })
+    };
+    if let Some(found) = find(&arg.name, false) {
+        return Some(found);
+    }
+
+    let (name, key) = arg.name.split_once('.')?;
+    let found = find(name, true)?;
+    if let Some(ArgValue::KV(value)) = &arg.value {
+        arg.value = Some(ArgValue::Entry {
+            key: key.into(),
+            value: value.clone(),
+        });
+        arg.name = name.into();
+    }
+    Some(found)
+}
+
+fn check_constraints(constraints: &[Constraint], is_given: impl Fn(&str) -> bool) -> Result<()> {
+    for constraint in constraints {
+        match *constraint {
+            Constraint::ConflictsWith(name, other) if is_given(name) && is_given(other) => {
+                return Err(CmdArgError::ConflictingArguments { name, other });
+            }
+            Constraint::Requires(name, required) if is_given(name) && !is_given(required) => {
+                return Err(CmdArgError::MissingRequirement { name, required });
+            }
+            Constraint::AtLeastOneOf(names) | Constraint::ExactlyOneOf(names) => {
+                let given = names
+                    .iter()
+                    .copied()
+                    .filter(|name| is_given(name))
+                    .collect::<Vec<_>>();
+                if given.is_empty() {
+                    return Err(CmdArgError::MissingOneOf(names.to_vec()));
+                }
+                if given.len() > 1 && matches!(constraint, Constraint::ExactlyOneOf(_)) {
+                    return Err(CmdArgError::MoreThanOneOf(given));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+// The name of the named field closest to a mistyped one
+fn suggest<A>(fields: &[Field<A>], name: &str) -> Option<&'static str> {
+    let candidates = fields
+        .iter()
+        .filter(|field| matches!(field.kind, FieldKind::Named { .. }))
+        .flat_map(|field| {
+            [field.name]
+                .into_iter()
+                .chain(field.aliases.iter().copied())
+        });
+    closest(candidates, name)
+}
+
+// Allowing about one typo per three chars
+fn closest(candidates: impl IntoIterator<Item = &'static str>, name: &str) -> Option<&'static str> {
+    let name = name.to_lowercase();
+    candidates
+        .into_iter()
+        .map(|candidate| (edit_distance(&name, candidate), candidate))
+        .filter(|(distance, candidate)| *distance <= candidate.chars().count().div_ceil(3))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+// Levenshtein distance, in chars
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, a_ch) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, b_ch) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(a_ch != *b_ch);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(row[j + 1] + 1);
+        }
+    }
+
+    row[b.len()]
+}
+
+// Checked when `#[derive(Args)]` is expanded, so that a bad definition fails
+// to compile
+pub const fn check_fields<A>(fields: &[Field<A>]) {
+    let mut index = 0;
+    let mut seen_optional = false;
+    let mut seen_rest = false;
+
+    while index < fields.len() {
+        let required = match fields[index].kind {
+            FieldKind::Named {
+                form,
+                duplicates,
+                delimiter,
+                ..
+            } => {
+                if fields[index].default.is_some()
+                    && matches!(form, NamedForm::Flag | NamedForm::Switch | NamedForm::Map)
+                {
+                    panic!("only fields taking a value can have a default");
+                }
+                if delimiter.is_some() && !matches!(form, NamedForm::List) {
+                    panic!("only list fields can have a delimiter");
+                }
+                if matches!(duplicates, Duplicates::Collect) && !matches!(form, NamedForm::List) {
+                    panic!("only list fields can collect duplicates");
+                }
+                index += 1;
+                continue;
+            }
+            FieldKind::Positional { required, .. } | FieldKind::Rest { required, .. } => {
+                required && fields[index].default.is_none()
+            }
+        };
+
+        if seen_rest {
+            panic!("the rest argument must be the last positional one");
+        }
+        if required && seen_optional {
+            panic!("a required positional argument can't follow an optional one");
+        }
+        seen_optional |= !required;
+        seen_rest = matches!(fields[index].kind, FieldKind::Rest { .. });
+        index += 1;
+    }
+}
+
+// A shell-like token, with quotes and escapes resolved
+struct Token {
+    // Byte offset in the input
+    start: usize,
+    value: String,
+    // Byte offset in `value` of the first `=` not quoted or escaped
+    separator: Option<usize>,
+    // Whether the first char of `value` was quoted or escaped, so that e.g.
+    // `"-1"` isn't taken for a `-name` argument
+    literal_start: bool,
+}
+
+impl Token {
+    fn new(start: usize) -> Self {
+        Self {
+            start,
+            value: String::new(),
+            separator: None,
+            literal_start: false,
+        }
+    }
+
+    fn push(&mut self, ch: char, literal: bool) {
+        if self.value.is_empty() {
+            self.literal_start = literal;
+        }
+        if ch == '=' && !literal && self.separator.is_none() {
+            self.separator = Some(self.value.len());
+        }
+        self.value.push(ch);
+    }
+}
+
+// The quotes closing `open`, if it's an opening one. Telegram clients on iOS
+// and macOS replace straight quotes with smart ones as they're typed. Single
+// quotes only open at the start of a token, so that apostrophes as in
+// `didn't` are taken literally.
+fn closing_quotes(open: char, token_start: bool) -> Option<&'static [char]> {
+    match open {
+        '"' => Some(&['"']),
+        '\'' if token_start => Some(&['\'']),
+        '“' | '”' => Some(&['”', '“']),
+        '„' => Some(&['“', '”']),
+        '‘' | '’' if token_start => Some(&['’', '‘']),
+        _ => None,
+    }
+}
+
+// Whitespace separated tokens, read one at a time so that the rest of the
+// input can be taken verbatim. Single quotes take everything literally up to
+// the closing one, double quotes allow escaping the closing quote and
+// backslashes, and a backslash outside of quotes escapes any char.
+#[derive(Clone)]
+struct Tokenizer<'a> {
+    // With their position in chars
+    chars: std::iter::Enumerate<std::str::CharIndices<'a>>,
+}
+
+impl<'a> Tokenizer<'a> {
+    fn new(input: &'a str) -> Self {
+        Self {
+            chars: input.char_indices().enumerate(),
+        }
+    }
+
+    // The byte offset of the next token, skipping the whitespace before it
+    fn next_start(&mut self) -> Option<usize> {
+        while let Some((_, (start, ch))) = self.chars.clone().next() {
+            if !ch.is_whitespace() {
+                return Some(start);
+            }
+            self.chars.next();
+        }
+        None
+    }
+}
+
+impl Iterator for Tokenizer<'_> {
+    type Item = Result<Token>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut token = Token::new(self.next_start()?);
+        // The position of the opening quote, in chars, and the closing ones
+        let mut quote: Option<(usize, bool, &[char])> = None;
+
+        while let Some((position, (index, ch))) = self.chars.next() {
+            if let Some((_, escapes, closing)) = quote {
+                if closing.contains(&ch) {
+                    quote = None;
+                    continue;
+                }
+                if escapes && ch == '\\' {
+                    let next = self.chars.clone().next().map(|(_, (_, next))| next);
+                    if let Some(next) = next.filter(|next| *next == '\\' || closing.contains(next))
+                    {
+                        self.chars.next();
+                        token.push(next, true);
+                        continue;
+                    }
+                }
+                token.push(ch, true);
+                continue;
+            }
+
+            if ch.is_whitespace() {
+                break;
+            }
+
+            if ch == '\\' {
+                let Some((_, (_, next))) = self.chars.next() else {
+                    return Some(Err(CmdArgError::DanglingEscape(position)));
+                };
+                token.push(next, true);
+            } else if let Some(closing) = closing_quotes(ch, index == token.start) {
+                let escapes = !matches!(ch, '\'' | '‘' | '’');
+                quote = Some((position, escapes, closing));
+            } else {
+                token.push(ch, false);
+            }
+        }
+
+        if let Some((position, ..)) = quote {
+            return Some(Err(CmdArgError::UnterminatedQuote(position)));
+        }
+        Some(Ok(token))
+    }
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>> {
+    Tokenizer::new(input).collect()
+}
+
+// Whether the next token is a named argument. An invalid or quoted one is left
+// to the rest field.
+fn is_named_next<A>(fields: &[Field<A>], mut tokens: Tokenizer) -> bool {
+    let Some(Ok(token)) = tokens.next() else {
+        return false;
+    };
+    !token.literal_start
+        && Arg::parse(&token).is_ok_and(|mut arg| find_named(fields, &mut arg).is_some())
+}
+
 pub struct Arg {
     name: String,
     value: Option<ArgValue>,
 }
 
 impl Arg {
-    fn parse(input: impl AsRef<str>) -> Result<Self> {
-        let input = input.as_ref();
+    fn parse(token: &Token) -> Result<Self> {
+        let input = token.value.as_str();
 
         let (sign, kv) = (
             {
                 let mut iter = input.chars();
                 iter.next()
-                    .filter(|&ch| ch == '+' || ch == '-')
+                    .filter(|&ch| !token.literal_start && (ch == '+' || ch == '-'))
                     .zip(Some(iter.as_str()))
             },
-            input.split_once('='),
+            token
+                .separator
+                .map(|index| (&input[..index], &input[index + 1..])),
         );
 
         let arg = match (sign, kv) {
@@ -69,9 +854,7 @@ impl Arg {
                 name: name.into(),
                 value: Some(ArgValue::Bool(ch == '+')),
             },
-            (Some(_), Some(_)) => {
-                return Err(CmdArgError::UnsupportedBoolKVOption);
-            }
+            (Some(_), Some(_)) => return Err(CmdArgError::SignedValue(input.into())),
             (None, None) => Self {
                 name: input.into(),
                 value: None,
@@ -80,15 +863,25 @@ impl Arg {
 
         Ok(arg)
     }
+
+    // Whether a flag or a switch is turned off, reset included
+    fn is_off(&self) -> bool {
+        match &self.value {
+            Some(ArgValue::Bool(enable)) => !enable,
+            Some(ArgValue::KV(value)) => parse_bool(value) != Some(true),
+            _ => false,
+        }
+    }
 }
 
 impl fmt::Display for Arg {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match &self.value {
             Some(ArgValue::Bool(enable)) => {
-                write!(f, "{}{}", *enable, self.name)
+                write!(f, "{}{}", if *enable { '+' } else { '-' }, self.name)
             }
             Some(ArgValue::KV(value)) => write!(f, "{}={}", self.name, value),
+            Some(ArgValue::Entry { key, value }) => write!(f, "{}.{}={}", self.name, key, value),
             None => write!(f, "{}", self.name),
         }
     }
@@ -97,84 +890,386 @@ impl fmt::Display for Arg {
 pub enum ArgValue {
     Bool(bool), // `-opt`, `+opt`
     KV(String), // `arg=abc`
+    // `arg.key=abc`, for map fields
+    Entry { key: String, value: String },
 }
 
-#[macro_export]
-macro_rules! define_cmd_args {
-    ( $help:literal $(#[$attrs:meta])* $vis:vis struct $name:ident { $($body:tt)* } ) => {
-        $(#[$attrs])*
-        $vis struct $name { $($body)* }
+// Values of named `name=value` and positional arguments
+pub trait FromArg: Sized {
+    // Shown to the user when a value can't be parsed, e.g. "an integer"
+    const EXPECTED: &'static str;
+
+    fn from_arg(value: &str) -> Option<Self>;
+
+    fn apply_arg(value: &str) -> std::result::Result<Self, ApplyError> {
+        Self::from_arg(value).ok_or(ApplyError::InvalidValue(Self::EXPECTED))
+    }
+}
+
+impl FromArg for String {
+    const EXPECTED: &'static str = "a string";
 
-        impl $crate::cmd_arg::Args for $name {
-            fn help() -> &'static str {
-                $help
+    fn from_arg(value: &str) -> Option<Self> {
+        Some(value.into())
+    }
+}
+
+macro_rules! impl_from_arg_parse {
+    ( $expected:literal => $($ty:ty),* ) => {
+        $(impl FromArg for $ty {
+            const EXPECTED: &'static str = $expected;
+
+            fn from_arg(value: &str) -> Option<Self> {
+                value.parse().ok()
             }
+        })*
+    };
+}
 
-            fn parse(input: impl AsRef<str>) -> std::result::Result<Self, $crate::cmd_arg::CmdArgError> {
-                Self::parse_inner(input, |args, name, value| {
-                    define_cmd_args!(@ARM, (name, value), args, $($body)*)
-                })
+impl_from_arg_parse!("an integer" => i8, i16, i32, i64, i128, isize);
+impl_from_arg_parse!("a non-negative integer" => u8, u16, u32, u64, u128, usize);
+
+macro_rules! impl_from_arg_float {
+    ( $($ty:ty),* ) => {
+        $(impl FromArg for $ty {
+            const EXPECTED: &'static str = "a number";
+
+            // `inf` and `NaN` are accepted by `parse` but never meant
+            fn from_arg(value: &str) -> Option<Self> {
+                value.parse().ok().filter(|value: &$ty| value.is_finite())
             }
-        }
+        })*
     };
-    ( @ARM, $input:expr, $result:expr,
-      $(#[$attrs:meta])* $vis:vis $name:ident : bool, $($body:tt)*) => {
-        if let (stringify!($name), None) = $input {
-            $result.$name = true;
-            return true;
-        } else {
-            define_cmd_args!(@ARM, $input, $result, $($body)*)
+}
+
+impl_from_arg_float!(f32, f64);
+
+impl FromArg for Duration {
+    const EXPECTED: &'static str = "a duration like `1h30m`";
+
+    // Numbers each followed by a unit of `w`, `d`, `h`, `m` or `s`
+    fn from_arg(value: &str) -> Option<Self> {
+        if value.is_empty() {
+            return None;
         }
-    };
-    ( @ARM, $input:expr, $result:expr,
-      $(#[$attrs:meta])* $vis:vis $name:ident : Option<bool>, $($body:tt)*) => {
-        if let (stringify!($name), Some($crate::cmd_arg::ArgValue::Bool(enable))) = $input {
-            $result.$name = Some(*enable);
-            return true;
-        } else {
-            define_cmd_args!(@ARM, $input, $result, $($body)*)
+
+        let mut secs: u64 = 0;
+        let mut rest = value;
+        while !rest.is_empty() {
+            let digits = rest.find(|ch: char| !ch.is_ascii_digit())?;
+            let count: u64 = rest[..digits].parse().ok()?;
+            let mut chars = rest[digits..].chars();
+            let unit = match chars.next()? {
+                'w' => 7 * 24 * 60 * 60,
+                'd' => 24 * 60 * 60,
+                'h' => 60 * 60,
+                'm' => 60,
+                's' => 1,
+                _ => return None,
+            };
+            secs = secs.checked_add(count.checked_mul(unit)?)?;
+            rest = chars.as_str();
         }
-    };
-    ( @ARM, $input:expr, $result:expr,
-      $(#[$attrs:meta])* $vis:vis $name:ident : Option<String>, $($body:tt)*) => {
-        if let (stringify!($name), Some(ArgValue::KV(value))) = $input {
-            $result.$name = Some(value.into());
-            return true;
-        } else {
-            define_cmd_args!(@ARM, $input, $result, $($body)*)
+
+        Some(Duration::from_secs(secs))
+    }
+}
+
+impl FromArg for Url {
+    const EXPECTED: &'static str = "a URL";
+
+    fn from_arg(value: &str) -> Option<Self> {
+        Url::parse(value).ok()
+    }
+}
+
+// The range of ids teloxide accepts, from channels to users
+const MIN_CHAT_ID: i64 = -1_997_852_516_352;
+const MAX_CHAT_ID: i64 = (1 << 40) - 1;
+
+impl FromArg for ChatId {
+    const EXPECTED: &'static str = "a chat id";
+
+    fn from_arg(value: &str) -> Option<Self> {
+        let id: i64 = value.parse().ok()?;
+        (id != 0 && (MIN_CHAT_ID..=MAX_CHAT_ID).contains(&id)).then_some(ChatId(id))
+    }
+}
+
+impl FromArg for TgLink {
+    const EXPECTED: &'static str = "a Telegram link";
+
+    // Links are often pasted without their scheme, e.g. `t.me/c/123/456`
+    fn from_arg(value: &str) -> Option<Self> {
+        let url = match Url::parse(value) {
+            Ok(url) => url,
+            Err(_) => Url::parse(&format!("https://{value}")).ok()?,
+        };
+        parse_tg_link(&url)
+    }
+}
+
+impl FromArg for MessageLink {
+    const EXPECTED: &'static str = "a message link";
+
+    fn from_arg(value: &str) -> Option<Self> {
+        match TgLink::from_arg(value)? {
+            TgLink::Message(link) => Some(link),
+            _ => None,
         }
-    };
-    ( @ARM, $input:expr, $result:expr,) => {
-      false
-    };
+    }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+// A user given as `@username`, a user id or a `tg://user?id=` link
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum UserMention {
+    Username(String),
+    Id(UserId),
+}
+
+impl FromArg for UserMention {
+    const EXPECTED: &'static str = "a user mention";
 
-    define_cmd_args! {
-        "help text"
+    fn from_arg(value: &str) -> Option<Self> {
+        if let Ok(id) = value.parse::<u64>() {
+            let is_valid = id != 0 && id <= MAX_CHAT_ID as u64;
+            return is_valid.then_some(Self::Id(UserId(id)));
+        }
 
-        #[derive(PartialEq, Eq, Debug, Default)]
-        pub struct TestArgs {
-            pub help: bool,
-            opt_bool: Option<bool>,
-            opt_string: Option<String>,
+        let link = match value.strip_prefix('@') {
+            Some(username) => TgLink::from_arg(&format!("t.me/{username}"))?,
+            None => TgLink::from_arg(value)?,
+        };
+        match link {
+            TgLink::Username(username) => Some(Self::Username(username)),
+            TgLink::User(id) => Some(Self::Id(id)),
+            _ => None,
         }
     }
+}
 
-    #[test]
-    fn validation() {
-        assert_eq!(TestArgs::help(), "help text");
-        assert_eq!(
-            TestArgs::parse("").unwrap(),
-            TestArgs {
-                help: false,
-                opt_bool: None,
-                opt_string: None,
+// Implements `FromArg` for an enum of plain variants, each matching a fixed
+// value
+#[macro_export]
+macro_rules! define_arg_enum {
+    ( $(#[$attrs:meta])* $vis:vis enum $name:ident {
+        $(#[$first_attrs:meta])* $first:ident => $first_value:literal
+        $(, $(#[$vattrs:meta])* $variant:ident => $value:literal)* $(,)?
+    } ) => {
+        $(#[$attrs])*
+        $vis enum $name {
+            $(#[$first_attrs])* $first,
+            $($(#[$vattrs])* $variant,)*
+        }
+
+        impl $crate::cmd_arg::FromArg for $name {
+            const EXPECTED: &'static str =
+                concat!("one of `", $first_value, "`" $(, ", `", $value, "`")*);
+
+            fn from_arg(value: &str) -> Option<Self> {
+                match value {
+                    $first_value => Some(Self::$first),
+                    $($value => Some(Self::$variant),)*
+                    _ => None,
+                }
             }
-        );
+        }
+    };
+}
+
+// The field types accepted by `#[derive(Args)]` for named arguments
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` can't be the type of a named argument",
+    note = "named arguments are `bool`, `Option<bool>`, `Option<T>`, `Vec<T>`, `HashMap<String, \
+            T>` or `T` where `T: FromArg + Default`, or use `#[arg(value_parser = ...)]`"
+)]
+pub trait NamedArg {
+    const FORM: NamedForm;
+
+    fn apply(&mut self, value: Option<&ArgValue>) -> ApplyResult;
+
+    // Drops the values of a list field given again
+    fn clear(&mut self) {}
+}
+
+macro_rules! bool_expected {
+    () => {
+        "`yes`, `no`, `true`, `false`, `on`, `off`, `1` or `0`"
+    };
+}
+
+const BOOL_EXPECTED: &str = bool_expected!();
+
+// Not a `FromArg` impl, which would make `bool` a value field
+fn parse_bool(value: &str) -> Option<bool> {
+    const VALUES: [(&str, bool); 8] = [
+        ("yes", true),
+        ("no", false),
+        ("true", true),
+        ("false", false),
+        ("on", true),
+        ("off", false),
+        ("1", true),
+        ("0", false),
+    ];
+    VALUES
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(value))
+        .map(|(_, enable)| *enable)
+}
+
+// `name`, `+name`, `-name` or `name=<bool>`
+impl NamedArg for bool {
+    const FORM: NamedForm = NamedForm::Flag;
+
+    fn apply(&mut self, value: Option<&ArgValue>) -> ApplyResult {
+        *self = match value {
+            None => true,
+            Some(ArgValue::Bool(enable)) => *enable,
+            Some(ArgValue::KV(value)) => {
+                parse_bool(value).ok_or(ApplyError::InvalidValue(BOOL_EXPECTED))?
+            }
+            _ => return Err(ApplyError::IllFormed),
+        };
+        Ok(())
+    }
+}
+
+// `+name`, `-name` or `name=<bool>`, `name=default` resets it to `None`
+impl NamedArg for Option<bool> {
+    const FORM: NamedForm = NamedForm::Switch;
+
+    fn apply(&mut self, value: Option<&ArgValue>) -> ApplyResult {
+        *self = match value {
+            Some(ArgValue::Bool(enable)) => Some(*enable),
+            Some(ArgValue::KV(value)) if value.eq_ignore_ascii_case("default") => None,
+            Some(ArgValue::KV(value)) => Some(parse_bool(value).ok_or(
+                ApplyError::InvalidValue(concat!("`default`, ", bool_expected!())),
+            )?),
+            _ => return Err(ApplyError::IllFormed),
+        };
+        Ok(())
+    }
+}
+
+impl<T: FromArg> NamedArg for Option<T> {
+    const FORM: NamedForm = NamedForm::Value;
+
+    fn apply(&mut self, value: Option<&ArgValue>) -> ApplyResult {
+        match value {
+            Some(ArgValue::KV(value)) => {
+                *self = Some(T::apply_arg(value)?);
+                Ok(())
+            }
+            _ => Err(ApplyError::IllFormed),
+        }
+    }
+}
+
+// Left as its default when not given
+impl<T: FromArg + Default> NamedArg for T {
+    const FORM: NamedForm = NamedForm::Value;
+
+    fn apply(&mut self, value: Option<&ArgValue>) -> ApplyResult {
+        match value {
+            Some(ArgValue::KV(value)) => {
+                *self = T::apply_arg(value)?;
+                Ok(())
+            }
+            _ => Err(ApplyError::IllFormed),
+        }
+    }
+}
+
+impl<T: FromArg> NamedArg for Vec<T> {
+    const FORM: NamedForm = NamedForm::List;
+
+    fn apply(&mut self, value: Option<&ArgValue>) -> ApplyResult {
+        match value {
+            Some(ArgValue::KV(value)) => {
+                self.push(T::apply_arg(value)?);
+                Ok(())
+            }
+            _ => Err(ApplyError::IllFormed),
+        }
+    }
+
+    fn clear(&mut self) {
+        Vec::clear(self);
+    }
+}
+
+impl<T: FromArg> NamedArg for HashMap<String, T> {
+    const FORM: NamedForm = NamedForm::Map;
+
+    fn apply(&mut self, value: Option<&ArgValue>) -> ApplyResult {
+        match value {
+            Some(ArgValue::Entry { key, value }) => {
+                self.insert(key.clone(), T::apply_arg(value)?);
+                Ok(())
+            }
+            _ => Err(ApplyError::IllFormed),
+        }
+    }
+}
+
+// The field types accepted by `#[derive(Args)]` for positional and rest
+// arguments. `Option`s are optional, anything else is required.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` can't be the type of a positional argument",
+    note = "positional arguments are `Option<T>` or `T` where `T: FromArg`, or use \
+            `#[arg(value_parser = ...)]`"
+)]
+pub trait PositionalArg {
+    const REQUIRED: bool;
+
+    fn apply(&mut self, value: &str) -> ApplyResult;
+}
+
+impl<T: FromArg> PositionalArg for T {
+    const REQUIRED: bool = true;
+
+    fn apply(&mut self, value: &str) -> ApplyResult {
+        *self = T::apply_arg(value)?;
+        Ok(())
+    }
+}
+
+impl<T: FromArg> PositionalArg for Option<T> {
+    const REQUIRED: bool = false;
+
+    fn apply(&mut self, value: &str) -> ApplyResult {
+        *self = Some(T::apply_arg(value)?);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use teloxide::types::{ChatId, MessageEntityKind, MessageId, UserId};
+
+    use super::*;
+    use crate::text::LinkChat;
+
+    #[derive(Args, PartialEq, Eq, Debug, Default)]
+    #[arg(about = "help text")]
+    pub struct TestArgs {
+        pub help: bool,
+        opt_bool: Option<bool>,
+        opt_string: Option<String>,
+    }
+
+    #[test]
+    fn validation() {
+        assert_eq!(TestArgs::about(), Some("help text"));
+        assert_eq!(
+            TestArgs::parse("").unwrap(),
+            TestArgs {
+                help: false,
+                opt_bool: None,
+                opt_string: None,
+            }
+        );
 
         assert_eq!(
             TestArgs::parse("help").unwrap(),
@@ -184,9 +1279,42 @@ mod tests {
                 opt_string: None,
             }
         );
-        assert!(TestArgs::parse("+help").is_err());
-        assert!(TestArgs::parse("-help").is_err());
-        assert!(TestArgs::parse("help=abc").is_err());
+        for (input, help) in [
+            ("+help", true),
+            ("-help", false),
+            ("help=yes", true),
+            ("help=no", false),
+            ("help=true", true),
+            ("help=false", false),
+            ("help=On", true),
+            ("help=OFF", false),
+            ("help=1", true),
+            ("help=0", false),
+        ] {
+            assert_eq!(
+                TestArgs::parse(input).unwrap(),
+                TestArgs {
+                    help,
+                    opt_bool: None,
+                    opt_string: None,
+                },
+                "{input}"
+            );
+        }
+        assert!(matches!(
+            TestArgs::parse("help=abc"),
+            Err(CmdArgError::InvalidValue { name: "help", .. })
+        ));
+        assert!(matches!(
+            TestArgs::parse("help=default"),
+            Err(CmdArgError::InvalidValue { name: "help", .. })
+        ));
+        let error = TestArgs::parse("+help=yes").unwrap_err();
+        assert!(matches!(&error, CmdArgError::SignedValue(arg) if arg == "+help=yes"));
+        assert_eq!(
+            error.to_string(),
+            "argument `+help=yes` can't have both a sign and a value"
+        );
 
         assert_eq!(
             TestArgs::parse("+opt_bool").unwrap(),
@@ -204,8 +1332,36 @@ mod tests {
                 opt_string: None,
             }
         );
+        for (input, opt_bool) in [
+            ("opt_bool=yes", Some(true)),
+            ("opt_bool=no", Some(false)),
+            ("opt_bool=true", Some(true)),
+            ("opt_bool=false", Some(false)),
+            ("opt_bool=on", Some(true)),
+            ("opt_bool=off", Some(false)),
+            ("opt_bool=1", Some(true)),
+            ("opt_bool=0", Some(false)),
+            ("opt_bool=default", None),
+        ] {
+            assert_eq!(
+                TestArgs::parse(input).unwrap(),
+                TestArgs {
+                    help: false,
+                    opt_bool,
+                    opt_string: None,
+                },
+                "{input}"
+            );
+        }
         assert!(TestArgs::parse("opt_bool").is_err());
-        assert!(TestArgs::parse("opt_bool=abc").is_err());
+        assert!(matches!(
+            TestArgs::parse("opt_bool=abc"),
+            Err(CmdArgError::InvalidValue { name: "opt_bool", value, .. }) if value == "abc"
+        ));
+        assert!(matches!(
+            TestArgs::parse("-opt_bool=off"),
+            Err(CmdArgError::SignedValue(arg)) if arg == "-opt_bool=off"
+        ));
 
         assert_eq!(
             TestArgs::parse("opt_string=abc").unwrap(),
@@ -218,5 +1374,1043 @@ mod tests {
         assert!(TestArgs::parse("opt_string").is_err());
         assert!(TestArgs::parse("+opt_string").is_err());
         assert!(TestArgs::parse("-opt_string").is_err());
+        assert!(TestArgs::parse("+opt_string=abc").is_err());
+    }
+
+    #[test]
+    fn suggestions() {
+        let error = TestArgs::parse("opt_strng=abc").unwrap_err();
+        assert!(matches!(
+            &error,
+            CmdArgError::UnknownArgument {
+                arg,
+                suggestion: Some("opt_string"),
+            } if arg == "opt_strng=abc"
+        ));
+        assert_eq!(
+            error.to_string(),
+            "unknown argument `opt_strng=abc`, did you mean `opt_string`?"
+        );
+        assert!(matches!(
+            TestArgs::parse("hepl"),
+            Err(CmdArgError::UnknownArgument {
+                suggestion: Some("help"),
+                ..
+            })
+        ));
+        assert!(matches!(
+            TestArgs::parse("-OPT_BOOOL"),
+            Err(CmdArgError::UnknownArgument {
+                suggestion: Some("opt_bool"),
+                ..
+            })
+        ));
+        let error = TestArgs::parse("meow").unwrap_err();
+        assert!(matches!(
+            error,
+            CmdArgError::UnknownArgument {
+                suggestion: None,
+                ..
+            }
+        ));
+        assert_eq!(error.to_string(), "unknown argument `meow`");
+
+        let error = TestArgs::parse("opt_bool").unwrap_err();
+        assert!(matches!(
+            &error,
+            CmdArgError::IllFormedArgument {
+                name: "opt_bool",
+                arg,
+                ..
+            } if arg == "opt_bool"
+        ));
+        assert_eq!(
+            error.to_string(),
+            "ill-formed argument `opt_bool`, expected `+opt_bool|-opt_bool|opt_bool=yes|no|default`"
+        );
+        assert!(matches!(
+            TestArgs::parse("opt_string"),
+            Err(CmdArgError::IllFormedArgument { name: "opt_string", expected, .. })
+                if expected == "opt_string=<value>"
+        ));
+    }
+
+    #[test]
+    fn edit_distance() {
+        assert_eq!(super::edit_distance("", ""), 0);
+        assert_eq!(super::edit_distance("previw", "preview"), 1);
+        assert_eq!(super::edit_distance("kitten", "sitting"), 3);
+        assert_eq!(super::edit_distance("喵喵", "喵"), 1);
+        assert_eq!(super::edit_distance("", "meow"), 4);
+    }
+
+    #[derive(Args, PartialEq, Eq, Debug, Default)]
+    #[arg(about = "link help text")]
+    struct LinkArgs {
+        msg: Option<MessageLink>,
+        target: Option<TgLink>,
+    }
+
+    #[test]
+    fn links() {
+        let msg = MessageLink::new(LinkChat::Id(ChatId(-1001234567890)), MessageId(42));
+
+        assert_eq!(
+            LinkArgs::parse("msg=https://t.me/c/1234567890/42").unwrap(),
+            LinkArgs {
+                msg: Some(msg.clone()),
+                target: None,
+            }
+        );
+        assert_eq!(
+            LinkArgs::parse("msg=t.me/c/1234567890/42 target=t.me/catnews").unwrap(),
+            LinkArgs {
+                msg: Some(msg),
+                target: Some(TgLink::Username("catnews".into())),
+            }
+        );
+        assert_eq!(
+            LinkArgs::parse("target=tg://user?id=42").unwrap(),
+            LinkArgs {
+                msg: None,
+                target: Some(TgLink::User(UserId(42))),
+            }
+        );
+        assert!(LinkArgs::parse("msg=t.me/catnews").is_err());
+        assert!(LinkArgs::parse("target=meow").is_err());
+    }
+
+    #[derive(Args, PartialEq, Eq, Debug, Default)]
+    #[arg(about = "ban help text")]
+    struct BanArgs {
+        #[arg(positional)]
+        target: String,
+        silent: bool,
+        #[arg(positional)]
+        duration: Option<String>,
+        #[arg(rest)]
+        reason: Option<String>,
+    }
+
+    #[derive(Args, PartialEq, Eq, Debug, Default)]
+    #[arg(about = "lookup help text")]
+    struct LookupArgs {
+        bot: bool,
+        id: Option<u64>,
+        #[arg(positional)]
+        name: String,
+        #[arg(positional)]
+        period: Option<String>,
+    }
+
+    #[derive(Args, PartialEq, Eq, Debug, Default)]
+    #[arg(about = "note help text")]
+    struct NoteArgs {
+        silent: bool,
+        #[arg(rest)]
+        text: String,
+    }
+
+    #[test]
+    fn positional() {
+        assert_eq!(
+            BanArgs::parse("@cat").unwrap(),
+            BanArgs {
+                target: "@cat".into(),
+                silent: false,
+                duration: None,
+                reason: None,
+            }
+        );
+        assert_eq!(
+            BanArgs::parse("@cat silent 1d  stole  the fish ").unwrap(),
+            BanArgs {
+                target: "@cat".into(),
+                silent: true,
+                duration: Some("1d".into()),
+                reason: Some("stole  the fish".into()),
+            }
+        );
+        // Named arguments still apply with the rest next, up to its first token
+        assert_eq!(
+            BanArgs::parse("@cat 1d silent stole").unwrap(),
+            BanArgs {
+                target: "@cat".into(),
+                silent: true,
+                duration: Some("1d".into()),
+                reason: Some("stole".into()),
+            }
+        );
+        assert_eq!(
+            NoteArgs::parse("silent x").unwrap(),
+            NoteArgs {
+                silent: true,
+                text: "x".into(),
+            }
+        );
+        assert_eq!(
+            NoteArgs::parse("silent=no 'silent' it is").unwrap(),
+            NoteArgs {
+                silent: false,
+                text: "'silent' it is".into(),
+            }
+        );
+        assert_eq!(
+            NoteArgs::parse("x silent").unwrap(),
+            NoteArgs {
+                silent: false,
+                text: "x silent".into(),
+            }
+        );
+        assert!(matches!(
+            NoteArgs::parse("silent"),
+            Err(CmdArgError::MissingArgument("text"))
+        ));
+        // Named arguments are only recognized before the rest
+        assert_eq!(
+            BanArgs::parse("@cat 1d meow silent").unwrap(),
+            BanArgs {
+                target: "@cat".into(),
+                silent: false,
+                duration: Some("1d".into()),
+                reason: Some("meow silent".into()),
+            }
+        );
+        // The rest isn't tokenized, apostrophes and quotes included
+        for reason in ["he didn't pay", "he didn’t pay", "\"he 'said"] {
+            assert_eq!(
+                BanArgs::parse(format!("@cat 1d {reason}"))
+                    .unwrap()
+                    .reason
+                    .as_deref(),
+                Some(reason)
+            );
+        }
+
+        assert!(matches!(
+            BanArgs::parse(""),
+            Err(CmdArgError::MissingArgument("target"))
+        ));
+        assert!(matches!(
+            BanArgs::parse("silent"),
+            Err(CmdArgError::MissingArgument("target"))
+        ));
+        // A value close to a name still fills a free positional slot, unless
+        // written like a named argument
+        assert_eq!(
+            BanArgs::parse("@cat slient 1d").unwrap(),
+            BanArgs {
+                target: "@cat".into(),
+                silent: false,
+                duration: Some("slient".into()),
+                reason: Some("1d".into()),
+            }
+        );
+        assert!(matches!(
+            BanArgs::parse("@cat slient=yes"),
+            Err(CmdArgError::UnknownArgument { arg, suggestion: Some("silent") }) if arg == "slient=yes"
+        ));
+        assert!(matches!(
+            BanArgs::parse("+slient"),
+            Err(CmdArgError::UnknownArgument {
+                suggestion: Some("silent"),
+                ..
+            })
+        ));
+        assert_eq!(
+            LookupArgs::parse("bob 1d").unwrap(),
+            LookupArgs {
+                bot: false,
+                id: None,
+                name: "bob".into(),
+                period: Some("1d".into()),
+            }
+        );
+        // With no positional slot left
+        assert!(matches!(
+            LookupArgs::parse("bob 1d idd"),
+            Err(CmdArgError::UnknownArgument { arg, suggestion: Some("id") }) if arg == "idd"
+        ));
+        assert_eq!(
+            BanArgs::parse("@cat \"slient\"")
+                .unwrap()
+                .duration
+                .as_deref(),
+            Some("slient")
+        );
+        assert!(matches!(
+            LinkArgs::parse("t.me/c/1234567890/42"),
+            Err(CmdArgError::UnknownArgument { arg, suggestion: None }) if arg == "t.me/c/1234567890/42"
+        ));
+    }
+
+    #[derive(Args, PartialEq, Eq, Debug, Default)]
+    #[arg(about = "link help text")]
+    struct PositionalLinkArgs {
+        #[arg(positional)]
+        msg: Option<MessageLink>,
+    }
+
+    #[test]
+    fn positional_invalid_value() {
+        assert_eq!(
+            PositionalLinkArgs::parse("t.me/c/1234567890/42")
+                .unwrap()
+                .msg
+                .map(|msg| msg.msg_id()),
+            Some(MessageId(42))
+        );
+        assert!(matches!(
+            PositionalLinkArgs::parse("t.me/catnews"),
+            Err(CmdArgError::InvalidValue { name: "msg", value, expected: "a message link" }) if value == "t.me/catnews"
+        ));
+    }
+
+    #[derive(PartialEq, Eq, Debug, Default)]
+    struct ManualArgs {
+        verbose: bool,
+        title: Option<String>,
+    }
+
+    impl Args for ManualArgs {
+        const CONSTRAINTS: &'static [Constraint] = &[Constraint::Requires("title", "verbose")];
+
+        fn about() -> Option<&'static str> {
+            Some("manual help text")
+        }
+
+        fn parse(input: impl AsRef<str>) -> Result<Self> {
+            Self::parse_inner(input, |args, name, value| match (name, value) {
+                ("verbose", None) => {
+                    args.verbose = true;
+                    true
+                }
+                ("verbose", Some(ArgValue::KV(value))) => {
+                    args.verbose = value == "yes";
+                    true
+                }
+                ("title", Some(ArgValue::KV(title))) => {
+                    args.title = Some(title.clone());
+                    true
+                }
+                _ => false,
+            })
+        }
+    }
+
+    #[test]
+    fn parse_inner() {
+        assert_eq!(
+            ManualArgs::parse("verbose title=\"a b\"").unwrap(),
+            ManualArgs {
+                verbose: true,
+                title: Some("a b".into()),
+            }
+        );
+        assert!(matches!(
+            ManualArgs::parse("+verbose"),
+            Err(CmdArgError::UnknownArgument { arg, suggestion: None }) if arg == "+verbose"
+        ));
+        assert!(ManualArgs::FIELDS.is_empty());
+
+        // The constraints are checked too, an argument turned off not counting
+        assert!(matches!(
+            ManualArgs::parse("title=meow"),
+            Err(CmdArgError::MissingRequirement {
+                name: "title",
+                required: "verbose"
+            })
+        ));
+        assert!(matches!(
+            ManualArgs::parse("verbose title=meow verbose=no"),
+            Err(CmdArgError::MissingRequirement { .. })
+        ));
+        assert_eq!(
+            ManualArgs::parse("verbose=yes title=meow").unwrap(),
+            ManualArgs {
+                verbose: true,
+                title: Some("meow".into()),
+            }
+        );
+
+        // Without fields, the help is only the about
+        assert_eq!(ManualArgs::usage(), "");
+        assert_eq!(ManualArgs::help().text(), "manual help text");
+        assert_eq!(<()>::about(), None);
+        assert_eq!(<()>::help().text(), "");
+    }
+
+    #[test]
+    fn arg_display() {
+        let args = super::tokenize("+opt -opt opt opt=a map.key=b")
+            .unwrap()
+            .iter()
+            .map(|token| Arg::parse(token).unwrap().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(args, ["+opt", "-opt", "opt", "opt=a", "map.key=b"]);
+    }
+
+    define_arg_enum! {
+        #[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+        enum Action {
+            #[default]
+            Mute => "mute",
+            Ban => "ban",
+        }
+    }
+
+    #[derive(Args, PartialEq, Debug, Default)]
+    #[arg(about = "typed help text")]
+    struct TypedArgs {
+        #[arg(positional)]
+        user: Option<UserMention>,
+        count: u32,
+        ratio: Option<f64>,
+        action: Action,
+        #[arg(positional)]
+        duration: Option<Duration>,
+    }
+
+    #[test]
+    fn typed_values() {
+        assert_eq!(
+            TypedArgs::parse("@cat").unwrap(),
+            TypedArgs {
+                user: Some(UserMention::Username("cat".into())),
+                count: 0,
+                ratio: None,
+                action: Action::Mute,
+                duration: None,
+            }
+        );
+        assert_eq!(
+            TypedArgs::parse("42 count=3 ratio=0.5 action=ban 1h30m").unwrap(),
+            TypedArgs {
+                user: Some(UserMention::Id(UserId(42))),
+                count: 3,
+                ratio: Some(0.5),
+                action: Action::Ban,
+                duration: Some(Duration::from_secs(90 * 60)),
+            }
+        );
+
+        assert!(matches!(
+            TypedArgs::parse("@cat count=-1"),
+            Err(CmdArgError::InvalidValue {
+                name: "count",
+                value,
+                expected: "a non-negative integer",
+            }) if value == "-1"
+        ));
+        assert!(matches!(
+            TypedArgs::parse("@cat action=kick"),
+            Err(CmdArgError::InvalidValue {
+                name: "action",
+                expected: "one of `mute`, `ban`",
+                ..
+            })
+        ));
+        assert!(matches!(
+            TypedArgs::parse("@cat 1.5h"),
+            Err(CmdArgError::InvalidValue {
+                name: "duration",
+                ..
+            })
+        ));
+        assert!(matches!(
+            TypedArgs::parse("meow"),
+            Err(CmdArgError::InvalidValue { name: "user", .. })
+        ));
+        assert!(matches!(
+            TypedArgs::parse("@cat count"),
+            Err(CmdArgError::IllFormedArgument { name: "count", .. })
+        ));
+    }
+
+    #[test]
+    fn from_arg() {
+        assert_eq!(Duration::from_arg("90s"), Some(Duration::from_secs(90)));
+        assert_eq!(
+            Duration::from_arg("1w2d"),
+            Some(Duration::from_secs(9 * 24 * 60 * 60))
+        );
+        assert_eq!(Duration::from_arg(""), None);
+        assert_eq!(Duration::from_arg("30"), None);
+        assert_eq!(Duration::from_arg("h"), None);
+        assert_eq!(Duration::from_arg("99999999999999999999w"), None);
+
+        assert_eq!(f64::from_arg("-1e3"), Some(-1000.0));
+        assert_eq!(f64::from_arg("inf"), None);
+        assert_eq!(f64::from_arg("NaN"), None);
+
+        assert_eq!(
+            ChatId::from_arg("-1001234567890"),
+            Some(ChatId(-1001234567890))
+        );
+        assert_eq!(ChatId::from_arg("42"), Some(ChatId(42)));
+        assert_eq!(ChatId::from_arg("0"), None);
+        assert_eq!(ChatId::from_arg("-9999999999999"), None);
+
+        assert_eq!(
+            Url::from_arg("https://http.cat"),
+            Some("https://http.cat".try_into().unwrap())
+        );
+        assert_eq!(Url::from_arg("http.cat"), None);
+
+        assert_eq!(
+            UserMention::from_arg("tg://user?id=42"),
+            Some(UserMention::Id(UserId(42)))
+        );
+        assert_eq!(
+            UserMention::from_arg("t.me/cat"),
+            Some(UserMention::Username("cat".into()))
+        );
+        assert_eq!(UserMention::from_arg("@"), None);
+        assert_eq!(UserMention::from_arg("@c/1/2"), None);
+        assert_eq!(UserMention::from_arg("0"), None);
+    }
+
+    fn tokens(input: &str) -> Vec<String> {
+        super::tokenize(input)
+            .unwrap()
+            .into_iter()
+            .map(|token| token.value)
+            .collect()
+    }
+
+    #[test]
+    fn tokenize() {
+        assert_eq!(tokens("  a  b\tc\n"), ["a", "b", "c"]);
+        assert_eq!(tokens(r#"title="my new title""#), ["title=my new title"]);
+        assert_eq!(tokens(r#"a"b c"d 'e f'"#), ["ab cd", "e f"]);
+        assert_eq!(tokens(r#""" ''"#), ["", ""]);
+        assert_eq!(tokens(r"a\ b \'c\\"), ["a b", "'c\\"]);
+        assert_eq!(tokens(r#""\"\\\n" '\n'"#), [r#""\\n"#, r"\n"]);
+        assert_eq!(
+            tokens("“my title” ‘it's’ „meow“"),
+            ["my title", "it's", "meow"]
+        );
+        assert_eq!(tokens("喵 \"喵 喵\""), ["喵", "喵 喵"]);
+        // Single quotes only open at the start of a token
+        assert_eq!(tokens("didn't didn’t 'a b'c"), ["didn't", "didn’t", "a bc"]);
+
+        let token = &super::tokenize(r#"a\=b=c"d=e""#).unwrap()[0];
+        assert_eq!(token.value, "a=b=cd=e");
+        assert_eq!(token.separator, Some(3));
+
+        assert!(matches!(
+            super::tokenize(r#"喵 "meow"#),
+            Err(CmdArgError::UnterminatedQuote(2))
+        ));
+        assert!(matches!(
+            super::tokenize("meow ‘meow"),
+            Err(CmdArgError::UnterminatedQuote(5))
+        ));
+        assert!(matches!(
+            super::tokenize(r"meow\"),
+            Err(CmdArgError::DanglingEscape(4))
+        ));
+    }
+
+    #[test]
+    fn quoted_values() {
+        assert_eq!(
+            TestArgs::parse(r#"opt_string="my new title""#).unwrap(),
+            TestArgs {
+                help: false,
+                opt_bool: None,
+                opt_string: Some("my new title".into()),
+            }
+        );
+        assert_eq!(
+            TestArgs::parse(r"opt_string=a\=b").unwrap().opt_string,
+            Some("a=b".into())
+        );
+        // Quoted names are still names
+        assert!(TestArgs::parse(r#""help""#).unwrap().help);
+
+        assert_eq!(
+            BanArgs::parse(r#""@cat" "-1d" "a=b"  "stole" fish"#).unwrap(),
+            BanArgs {
+                target: "@cat".into(),
+                silent: false,
+                duration: Some("-1d".into()),
+                reason: Some(r#""a=b"  "stole" fish"#.into()),
+            }
+        );
+        assert!(matches!(
+            BanArgs::parse("@cat \"meow"),
+            Err(CmdArgError::UnterminatedQuote(5))
+        ));
+    }
+
+    /// Mutes a user.
+    #[derive(Args, PartialEq, Eq, Debug, Default)]
+    struct MuteArgs {
+        /// The user to mute
+        #[arg(positional)]
+        target: String,
+        /// Don't announce it
+        /// in the chat
+        silent: bool,
+        notify: Option<bool>,
+        #[arg(default = "3")]
+        warnings: u32,
+        /// How long for
+        #[arg(positional)]
+        #[arg(default = "1h")]
+        duration: Duration,
+        #[arg(rest)]
+        reason: Option<String>,
+    }
+
+    #[test]
+    fn defaults() {
+        assert_eq!(
+            MuteArgs::parse("@cat").unwrap(),
+            MuteArgs {
+                target: "@cat".into(),
+                silent: false,
+                notify: None,
+                warnings: 3,
+                duration: Duration::from_secs(60 * 60),
+                reason: None,
+            }
+        );
+        assert_eq!(
+            MuteArgs::parse("@cat warnings=1 1d meow").unwrap(),
+            MuteArgs {
+                target: "@cat".into(),
+                silent: false,
+                notify: None,
+                warnings: 1,
+                duration: Duration::from_secs(24 * 60 * 60),
+                reason: Some("meow".into()),
+            }
+        );
+    }
+
+    #[test]
+    fn help() {
+        assert_eq!(
+            MuteArgs::usage(),
+            "<target> [silent[=yes|no]] [+notify|-notify|notify=yes|no|default] [warnings=<value>] \
+             [duration] [reason...]"
+        );
+        assert_eq!(
+            BanArgs::usage(),
+            "<target> [silent[=yes|no]] [duration] [reason...]"
+        );
+        assert_eq!(
+            BanArgs::parse_or_usage("").unwrap_err().to_string(),
+            "missing required argument `target`\n\
+             Usage: <target> [silent[=yes|no]] [duration] [reason...]"
+        );
+        assert!(BanArgs::parse_or_usage("@cat").is_ok());
+
+        let help = MuteArgs::help();
+        assert_eq!(
+            help.text(),
+            "Mutes a user.\n\
+             \n\
+             Usage: <target> [silent[=yes|no]] [+notify|-notify|notify=yes|no|default] \
+             [warnings=<value>] [duration] [reason...]\n\
+             \n\
+             • target <target>\n\
+             The user to mute\n\
+             • silent [silent[=yes|no]]\n\
+             Don't announce it in the chat\n\
+             • notify [+notify|-notify|notify=yes|no|default]\n\
+             • warnings [warnings=<value>]\n\
+             (default: 3)\n\
+             • duration [duration]\n\
+             How long for (default: 1h)\n\
+             • reason [reason...]"
+        );
+        let entities = help.parse_entities();
+        assert_eq!(
+            entities
+                .iter()
+                .filter(|entity| *entity.kind() == MessageEntityKind::Bold)
+                .map(|entity| entity.text())
+                .collect::<Vec<_>>(),
+            ["target", "silent", "notify", "warnings", "duration", "reason"]
+        );
+        assert_eq!(
+            entities
+                .iter()
+                .filter(|entity| *entity.kind() == MessageEntityKind::Code)
+                .map(|entity| entity.text())
+                .take(3)
+                .collect::<Vec<_>>(),
+            [
+                "<target> [silent[=yes|no]] [+notify|-notify|notify=yes|no|default] \
+                 [warnings=<value>] [duration] [reason...]",
+                "<target>",
+                "[silent[=yes|no]]"
+            ]
+        );
+    }
+
+    fn parse_percent(value: &str) -> std::result::Result<u8, &'static str> {
+        value
+            .strip_suffix('%')
+            .and_then(|value| value.parse().ok())
+            .filter(|percent| *percent <= 100)
+            .ok_or("a percentage like `50%`")
+    }
+
+    /// Sets the slow mode.
+    ///
+    /// Admins are not affected.
+    #[derive(Args, PartialEq, Eq, Debug, Default)]
+    struct SlowArgs {
+        #[arg(rename = "type", alias = "kind", short = 't', required)]
+        kind: String,
+        #[arg(short = 's', help = "Don't announce it")]
+        silent: bool,
+        #[arg(value_parser = parse_percent, duplicates = "error")]
+        ratio: Option<u8>,
+        #[arg(positional, value_parser = parse_percent)]
+        limit: u8,
+        #[arg(positional, required)]
+        r#for: Option<Duration>,
+    }
+
+    #[test]
+    fn derive_attributes() {
+        assert_eq!(
+            SlowArgs::about(),
+            Some("Sets the slow mode.\nAdmins are not affected.")
+        );
+        assert_eq!(
+            SlowArgs::parse("type=text 50% 1h").unwrap(),
+            SlowArgs {
+                kind: "text".into(),
+                silent: false,
+                ratio: None,
+                limit: 50,
+                r#for: Some(Duration::from_secs(60 * 60)),
+            }
+        );
+        assert_eq!(
+            SlowArgs::parse("s t=media ratio=10% 100% 1m kind=all").unwrap(),
+            SlowArgs {
+                kind: "all".into(),
+                silent: true,
+                ratio: Some(10),
+                limit: 100,
+                r#for: Some(Duration::from_secs(60)),
+            }
+        );
+
+        assert!(matches!(
+            SlowArgs::parse("50% 1h"),
+            Err(CmdArgError::MissingArgument("type"))
+        ));
+        assert!(matches!(
+            SlowArgs::parse("t=media ratio=10% ratio=20% 50% 1h"),
+            Err(CmdArgError::DuplicateArgument(name)) if name == "ratio"
+        ));
+        assert!(matches!(
+            SlowArgs::parse("type=text 50%"),
+            Err(CmdArgError::MissingArgument("for"))
+        ));
+        assert!(matches!(
+            SlowArgs::parse("type=text 150% 1h"),
+            Err(CmdArgError::InvalidValue {
+                name: "limit",
+                expected: "a percentage like `50%`",
+                ..
+            })
+        ));
+        assert!(matches!(
+            SlowArgs::parse("type=text ratio=1 50% 1h"),
+            Err(CmdArgError::InvalidValue { name: "ratio", value, .. }) if value == "1"
+        ));
+        assert!(matches!(
+            SlowArgs::parse("50% 1h knd=text"),
+            Err(CmdArgError::UnknownArgument {
+                suggestion: Some("kind"),
+                ..
+            })
+        ));
+
+        assert_eq!(
+            SlowArgs::usage(),
+            "type=<value> [silent[=yes|no]] [ratio=<value>] <limit> <for>"
+        );
+        assert_eq!(
+            SlowArgs::help().text(),
+            "Sets the slow mode.\n\
+             Admins are not affected.\n\
+             \n\
+             Usage: type=<value> [silent[=yes|no]] [ratio=<value>] <limit> <for>\n\
+             \n\
+             • type type=<value>, kind, t\n\
+             • silent [silent[=yes|no]], s\n\
+             Don't announce it\n\
+             • ratio [ratio=<value>]\n\
+             • limit <limit>\n\
+             • for <for>"
+        );
+    }
+
+    #[test]
+    fn derive_compile_fail() {
+        trybuild::TestCases::new().compile_fail("tests/ui/*.rs");
+    }
+
+    #[derive(Args, PartialEq, Eq, Debug, Default)]
+    #[arg(about = "post help text")]
+    struct PostArgs {
+        #[arg(alias = "tag")]
+        tags: Vec<String>,
+        #[arg(delimiter = ',')]
+        chats: Vec<ChatId>,
+        #[arg(duplicates = "last")]
+        title: Option<String>,
+        #[arg(duplicates = "last", delimiter = ',', default = "en,ru")]
+        langs: Vec<String>,
+        meta: HashMap<String, String>,
+        #[arg(duplicates = "last")]
+        limits: HashMap<String, u32>,
+    }
+
+    #[test]
+    fn lists_and_maps() {
+        assert_eq!(
+            PostArgs::parse("").unwrap(),
+            PostArgs {
+                langs: vec!["en".into(), "ru".into()],
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            PostArgs::parse(
+                "tag=a chats=-1001234567890,42 tags=b title=meow langs=uk chats=7 title=purr \
+                 meta.source=rss meta.by=cat limits.daily=3 limits.daily=5 langs=de,,fr"
+            )
+            .unwrap(),
+            PostArgs {
+                tags: vec!["a".into(), "b".into()],
+                chats: vec![ChatId(-1001234567890), ChatId(42), ChatId(7)],
+                title: Some("purr".into()),
+                langs: vec!["de".into(), "fr".into()],
+                meta: HashMap::from([("source".into(), "rss".into()), ("by".into(), "cat".into())]),
+                limits: HashMap::from([("daily".into(), 5)]),
+            }
+        );
+
+        assert!(matches!(
+            PostArgs::parse("meta.by=cat meta.by=dog"),
+            Err(CmdArgError::DuplicateArgument(name)) if name == "meta.by"
+        ));
+        assert!(matches!(
+            PostArgs::parse("chats=1,meow"),
+            Err(CmdArgError::InvalidValue { name: "chats", value, .. }) if value == "meow"
+        ));
+        assert!(matches!(
+            PostArgs::parse("limits.daily=many"),
+            Err(CmdArgError::InvalidValue { name: "limits", value, .. }) if value == "many"
+        ));
+        assert!(matches!(
+            PostArgs::parse("meta=rss"),
+            Err(CmdArgError::IllFormedArgument { name: "meta", expected, .. })
+                if expected == "meta.<key>=<value>"
+        ));
+        assert!(matches!(
+            PostArgs::parse("+meta.by"),
+            Err(CmdArgError::IllFormedArgument { name: "meta", .. })
+        ));
+        assert!(matches!(
+            PostArgs::parse("tags.a=b"),
+            Err(CmdArgError::UnknownArgument { .. })
+        ));
+
+        assert_eq!(
+            PostArgs::usage(),
+            "[tags=<value>...] [chats=<value>,...] [title=<value>] [langs=<value>,...] \
+             [meta.<key>=<value>] [limits.<key>=<value>]"
+        );
+    }
+
+    #[derive(Args, PartialEq, Eq, Debug, Default)]
+    #[arg(
+        exactly_one_of("target", "everyone"),
+        at_least_one_of("ban", "mute", "warn")
+    )]
+    struct PunishArgs {
+        #[arg(positional)]
+        target: Option<UserMention>,
+        everyone: bool,
+        ban: bool,
+        mute: bool,
+        warn: bool,
+        #[arg(requires = "ban", conflicts_with = "mute")]
+        until: Option<Duration>,
+        #[arg(conflicts_with = "notify")]
+        silent: Option<bool>,
+        #[arg(default = "admins")]
+        notify: Option<String>,
+    }
+
+    #[test]
+    fn constraints() {
+        assert_eq!(
+            PunishArgs::parse("@cat ban until=1d +silent").unwrap(),
+            PunishArgs {
+                target: Some(UserMention::Username("cat".into())),
+                ban: true,
+                until: Some(Duration::from_secs(24 * 60 * 60)),
+                silent: Some(true),
+                notify: Some("admins".into()),
+                ..Default::default()
+            }
+        );
+        assert!(PunishArgs::parse("everyone mute warn notify=all").is_ok());
+
+        assert!(matches!(
+            PunishArgs::parse("@cat ban +silent notify=all"),
+            Err(CmdArgError::ConflictingArguments {
+                name: "silent",
+                other: "notify"
+            })
+        ));
+        assert!(matches!(
+            PunishArgs::parse("@cat ban mute until=1d"),
+            Err(CmdArgError::ConflictingArguments {
+                name: "until",
+                other: "mute"
+            })
+        ));
+        assert!(matches!(
+            PunishArgs::parse("@cat warn until=1d"),
+            Err(CmdArgError::MissingRequirement {
+                name: "until",
+                required: "ban"
+            })
+        ));
+        assert!(matches!(
+            PunishArgs::parse("ban"),
+            Err(CmdArgError::MissingOneOf(names)) if names == ["target", "everyone"]
+        ));
+        assert!(matches!(
+            PunishArgs::parse("@cat everyone ban"),
+            Err(CmdArgError::MoreThanOneOf(names)) if names == ["target", "everyone"]
+        ));
+        assert_eq!(
+            PunishArgs::parse("@cat").unwrap_err().to_string(),
+            "one of `ban`, `mute`, `warn` is required"
+        );
+
+        // Flags and switches turned off don't count as given
+        assert!(PunishArgs::parse("@cat ban -silent notify=all").is_ok());
+        assert!(PunishArgs::parse("@cat ban silent=default notify=all").is_ok());
+        for ban in ["-ban", "ban=no"] {
+            assert!(matches!(
+                PunishArgs::parse(format!("@cat warn {ban} until=1d")),
+                Err(CmdArgError::MissingRequirement {
+                    name: "until",
+                    required: "ban"
+                })
+            ));
+            assert!(matches!(
+                PunishArgs::parse(format!("@cat {ban}")),
+                Err(CmdArgError::MissingOneOf(_))
+            ));
+        }
+        assert!(matches!(
+            PunishArgs::parse("@cat -everyone ban"),
+            Ok(PunishArgs {
+                everyone: false,
+                ban: true,
+                ..
+            })
+        ));
+        assert!(matches!(
+            PunishArgs::parse("-everyone ban"),
+            Err(CmdArgError::MissingOneOf(names)) if names == ["target", "everyone"]
+        ));
+        assert!(PunishArgs::parse("@cat ban -ban warn").is_ok());
+    }
+
+    fn positional_kind(required: bool) -> FieldKind<()> {
+        FieldKind::Positional {
+            required,
+            apply: |_, _| Ok(()),
+        }
+    }
+
+    fn rest_kind(required: bool) -> FieldKind<()> {
+        FieldKind::Rest {
+            required,
+            apply: |_, _| Ok(()),
+        }
+    }
+
+    fn fields(kinds: impl IntoIterator<Item = FieldKind<()>>) -> Vec<Field<()>> {
+        kinds
+            .into_iter()
+            .map(|kind| Field {
+                name: "field",
+                aliases: &[],
+                kind,
+                docs: &[],
+                default: None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn check_fields_ordering() {
+        check_fields(&fields([
+            positional_kind(true),
+            positional_kind(false),
+            rest_kind(false),
+        ]));
+        check_fields(&fields([
+            positional_kind(true),
+            FieldKind::Named {
+                form: NamedForm::Flag,
+                required: false,
+                duplicates: Duplicates::Error,
+                delimiter: None,
+                apply: |_, _| Ok(()),
+                clear: |_| {},
+            },
+            rest_kind(true),
+        ]));
+    }
+
+    #[test]
+    #[should_panic(expected = "a required positional argument can't follow an optional one")]
+    fn check_fields_required_after_optional() {
+        check_fields(&fields([positional_kind(false), positional_kind(true)]));
+    }
+
+    #[test]
+    #[should_panic(expected = "only fields taking a value can have a default")]
+    fn check_fields_flag_default() {
+        check_fields(&[Field::<()> {
+            name: "field",
+            aliases: &[],
+            kind: FieldKind::Named {
+                form: NamedForm::Switch,
+                required: false,
+                duplicates: Duplicates::Error,
+                delimiter: None,
+                apply: |_, _| Ok(()),
+                clear: |_| {},
+            },
+            docs: &[],
+            default: Some("+"),
+        }]);
+    }
+
+    #[test]
+    #[should_panic(expected = "the rest argument must be the last positional one")]
+    fn check_fields_after_rest() {
+        check_fields(&fields([rest_kind(false), positional_kind(false)]));
     }
 }
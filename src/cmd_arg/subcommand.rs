@@ -0,0 +1,275 @@
+use super::{closest, Args, CmdArgError, Result, Tokenizer};
+use crate::text::{mtb, MessageText};
+
+// An enum whose variants are subcommands, each holding the `Args` of its
+// own, e.g. `/config get key` and `/config set key=value`
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` doesn't implement `Subcommands`",
+    note = "use `#[derive(Subcommands)]` on an enum"
+)]
+pub trait Subcommands: Sized + 'static {
+    // Generated by `#[derive(Subcommands)]`, in declaration order
+    const SUBCOMMANDS: &'static [Subcommand<Self>];
+
+    // The doc comment or `#[arg(about = "...")]` of the enum
+    fn about() -> Option<&'static str> {
+        None
+    }
+
+    fn names() -> Vec<&'static str> {
+        Self::SUBCOMMANDS
+            .iter()
+            .map(|subcommand| subcommand.name)
+            .collect()
+    }
+
+    // E.g. `get|set|reset ...`
+    fn usage() -> String {
+        format!("{} ...", Self::names().join("|"))
+    }
+
+    fn help() -> MessageText<'static> {
+        let mut builder = mtb().plain(Self::about().unwrap_or_default());
+        let separator = if Self::about().is_some() { "\n\n" } else { "" };
+        builder = builder
+            .plain(format!("{separator}Usage: "))
+            .code(Self::usage())
+            .plain("\n");
+
+        for subcommand in Self::SUBCOMMANDS {
+            builder = builder.plain("\n• ").bold(subcommand.name);
+            let usage = (subcommand.usage)();
+            if !usage.is_empty() {
+                builder = builder.plain(" ").code(usage);
+            }
+            for alias in subcommand.aliases {
+                builder = builder.plain(", ").code(*alias);
+            }
+
+            let description = subcommand.description();
+            if !description.is_empty() {
+                builder = builder.plain("\n").plain(description);
+            }
+        }
+
+        builder.build()
+    }
+
+    // The help of the arguments of a subcommand
+    fn subcommand_help(name: &str) -> Option<MessageText<'static>> {
+        Self::SUBCOMMANDS
+            .iter()
+            .find(|subcommand| subcommand.is_named(name))
+            .map(|subcommand| (subcommand.help)())
+    }
+
+    // The first token names the subcommand, the rest of the input is parsed
+    // as its arguments, untouched
+    fn parse(input: impl AsRef<str>) -> Result<Self> {
+        let input = input.as_ref();
+        let mut tokens = Tokenizer::new(input);
+        let Some(first) = tokens.next().transpose()? else {
+            return Err(CmdArgError::MissingSubcommand {
+                valid: Self::names(),
+            });
+        };
+
+        let Some(subcommand) = Self::SUBCOMMANDS
+            .iter()
+            .find(|subcommand| subcommand.is_named(&first.value))
+        else {
+            let candidates = Self::SUBCOMMANDS.iter().flat_map(|subcommand| {
+                [subcommand.name]
+                    .into_iter()
+                    .chain(subcommand.aliases.iter().copied())
+            });
+            return Err(CmdArgError::UnknownSubcommand {
+                suggestion: closest(candidates, &first.value),
+                name: first.value.clone(),
+                valid: Self::names(),
+            });
+        };
+
+        let rest = tokens.next_start().map_or("", |start| &input[start..]);
+        (subcommand.parse)(rest)
+    }
+}
+
+pub struct Subcommand<S> {
+    pub name: &'static str,
+    pub aliases: &'static [&'static str],
+    // Lines of the variant's doc comment, the `about` of its arguments is
+    // shown instead if there's none
+    pub docs: &'static [&'static str],
+    pub about: fn() -> Option<&'static str>,
+    // Of the arguments following the name
+    pub usage: fn() -> String,
+    pub help: fn() -> MessageText<'static>,
+    pub parse: fn(&str) -> Result<S>,
+}
+
+impl<S> Subcommand<S> {
+    fn is_named(&self, name: &str) -> bool {
+        self.name == name || self.aliases.contains(&name)
+    }
+
+    fn description(&self) -> String {
+        let docs = self
+            .docs
+            .iter()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty())
+            .collect::<Vec<_>>();
+        match docs.is_empty() {
+            true => (self.about)().unwrap_or_default().into(),
+            false => docs.join(" "),
+        }
+    }
+}
+
+// The arguments of subcommands without any
+impl Args for () {}
+
+#[cfg(test)]
+mod tests {
+    use teloxide::types::MessageEntityKind;
+
+    use super::*;
+    use crate::cmd_arg::Subcommands;
+
+    #[derive(Args, PartialEq, Eq, Debug, Default)]
+    #[arg(about = "Shows a setting")]
+    struct GetArgs {
+        #[arg(positional)]
+        key: String,
+    }
+
+    #[derive(Args, PartialEq, Eq, Debug, Default)]
+    #[arg(about = "Changes a setting")]
+    struct SetArgs {
+        #[arg(positional)]
+        key: String,
+        #[arg(positional)]
+        value: String,
+        silent: bool,
+    }
+
+    /// Manages the chat settings.
+    #[derive(Subcommands, PartialEq, Eq, Debug)]
+    enum ConfigCmd {
+        #[arg(alias = "show")]
+        Get(GetArgs),
+        Set(SetArgs),
+        /// Restores all the defaults
+        ResetAll,
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            ConfigCmd::parse("get lang").unwrap(),
+            ConfigCmd::Get(GetArgs { key: "lang".into() })
+        );
+        assert_eq!(
+            ConfigCmd::parse("  show   lang ").unwrap(),
+            ConfigCmd::Get(GetArgs { key: "lang".into() })
+        );
+        assert_eq!(
+            ConfigCmd::parse("set greeting \"hi there\" silent").unwrap(),
+            ConfigCmd::Set(SetArgs {
+                key: "greeting".into(),
+                value: "hi there".into(),
+                silent: true,
+            })
+        );
+        assert_eq!(ConfigCmd::parse("reset_all").unwrap(), ConfigCmd::ResetAll);
+        assert_eq!(
+            ConfigCmd::parse("set greeting don’t").unwrap(),
+            ConfigCmd::Set(SetArgs {
+                key: "greeting".into(),
+                value: "don’t".into(),
+                silent: false,
+            })
+        );
+
+        assert!(matches!(
+            ConfigCmd::parse(" "),
+            Err(CmdArgError::MissingSubcommand { valid }) if valid == ["get", "set", "reset_all"]
+        ));
+        assert!(matches!(
+            ConfigCmd::parse("sett lang en"),
+            Err(CmdArgError::UnknownSubcommand { name, suggestion: Some("set"), .. })
+                if name == "sett"
+        ));
+        assert!(matches!(
+            ConfigCmd::parse("shw lang"),
+            Err(CmdArgError::UnknownSubcommand {
+                suggestion: Some("show"),
+                ..
+            })
+        ));
+        assert_eq!(
+            ConfigCmd::parse("drop lang").unwrap_err().to_string(),
+            "unknown subcommand `drop` (expected one of `get`, `set`, `reset_all`)"
+        );
+        assert!(matches!(
+            ConfigCmd::parse("get"),
+            Err(CmdArgError::MissingArgument("key"))
+        ));
+        assert!(matches!(
+            ConfigCmd::parse("reset_all now"),
+            Err(CmdArgError::UnknownArgument { .. })
+        ));
+        assert!(matches!(
+            ConfigCmd::parse("\"get lang"),
+            Err(CmdArgError::UnterminatedQuote(0))
+        ));
+    }
+
+    #[test]
+    fn help() {
+        assert_eq!(ConfigCmd::usage(), "get|set|reset_all ...");
+
+        let help = ConfigCmd::help();
+        assert_eq!(
+            help.text(),
+            "Manages the chat settings.\n\
+             \n\
+             Usage: get|set|reset_all ...\n\
+             \n\
+             • get <key>, show\n\
+             Shows a setting\n\
+             • set <key> <value> [silent[=yes|no]]\n\
+             Changes a setting\n\
+             • reset_all\n\
+             Restores all the defaults"
+        );
+        assert_eq!(
+            help.parse_entities()
+                .iter()
+                .filter(|entity| *entity.kind() == MessageEntityKind::Bold)
+                .map(|entity| entity.text())
+                .collect::<Vec<_>>(),
+            ["get", "set", "reset_all"]
+        );
+        assert_eq!(
+            help.parse_entities()
+                .iter()
+                .filter(|entity| *entity.kind() == MessageEntityKind::Code)
+                .map(|entity| entity.text())
+                .collect::<Vec<_>>(),
+            [
+                "get|set|reset_all ...",
+                "<key>",
+                "show",
+                "<key> <value> [silent[=yes|no]]"
+            ]
+        );
+
+        assert_eq!(
+            ConfigCmd::subcommand_help("show").unwrap().text(),
+            GetArgs::help().text()
+        );
+        assert!(ConfigCmd::subcommand_help("drop").is_none());
+    }
+}
@@ -34,7 +34,7 @@ pub enum TeloxideError {
     Download(#[from] teloxide::errors::DownloadError),
 }
 
-pub use crate::cmd_arg::CmdArgError;
+pub use crate::{cmd_arg::CmdArgError, text::MarkupError};
 
 macro_rules! impl_from_for_internal_errors {
     ( $( $variant:ident { $($from:ty),+ $(,)? } ),+ $(,)? ) => {
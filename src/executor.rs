@@ -2,10 +2,10 @@ use teloxide::{
     payloads::SendMessage,
     prelude::*,
     requests::JsonRequest,
-    types::{ChatId, ReplyMarkup},
+    types::{ChatId, Message, ReplyMarkup},
 };
 
-use crate::{button::*, text::*};
+use crate::{button::*, error::*, text::*};
 
 pub struct MessageExecutor<'a> {
     bot: &'a Bot,
@@ -19,16 +19,78 @@ impl<'a> MessageExecutor<'a> {
     }
 
     pub fn send_message(self, chat_id: ChatId) -> JsonRequest<SendMessage> {
-        let entities: Vec<_> = self.text.entities().into();
-
-        let mut builder = self
-            .bot
-            .send_message(chat_id, self.text.text())
-            .entities(entities)
-            .disable_web_page_preview(self.text.disable_preview());
-        if let Some(buttons) = self.buttons {
-            builder = builder.reply_markup::<ReplyMarkup>(buttons.into())
+        send_message_request(self.bot, chat_id, &self.text, self.buttons)
+    }
+
+    // Sends the text split into chunks fitting Telegram's length limit, in order.
+    // Buttons are only attached to the last chunk.
+    pub async fn send_message_split(self, chat_id: ChatId) -> Result<Vec<Message>> {
+        let mut chunks = split_chunks(&self.text).peekable();
+        let mut buttons = self.buttons;
+        let mut msgs = vec![];
+
+        while let Some(chunk) = chunks.next() {
+            let buttons = chunks.peek().is_none().then(|| buttons.take()).flatten();
+            msgs.push(send_message_request(self.bot, chat_id, &chunk, buttons).await?);
+        }
+
+        Ok(msgs)
+    }
+}
+
+// Only the first chunk keeps the link preview options, the preview of the
+// others is disabled so that the message shows a single one
+fn split_chunks<'t>(text: &'t MessageText) -> impl Iterator<Item = MessageText<'static>> + 't {
+    text.split(MESSAGE_TEXT_LIMIT)
+        .enumerate()
+        .map(|(index, mut chunk)| {
+            if index > 0 {
+                chunk.set_link_preview(LinkPreview::disabled());
+            }
+            chunk
+        })
+}
+
+fn send_message_request(
+    bot: &Bot,
+    chat_id: ChatId,
+    text: &MessageText,
+    buttons: Option<MessageButtons>,
+) -> JsonRequest<SendMessage> {
+    let entities: Vec<_> = text.entities().into();
+
+    let mut builder = bot
+        .send_message(chat_id, text.text())
+        .entities(entities)
+        .link_preview_options(text.link_preview().into());
+    if let Some(buttons) = buttons {
+        builder = builder.reply_markup::<ReplyMarkup>(buttons.into())
+    }
+    builder
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_chunks_preview() {
+        let text = "a".repeat(MESSAGE_TEXT_LIMIT) + " https://http.cat";
+        let msg_text = mtb()
+            .plain(text.repeat(2))
+            .preview_url("https://http.cat".try_into().unwrap())
+            .build();
+
+        let chunks: Vec<_> = split_chunks(&msg_text).collect();
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[0].link_preview(), msg_text.link_preview());
+        for chunk in &chunks[1..] {
+            assert_eq!(chunk.link_preview(), &LinkPreview::disabled());
         }
-        builder
+
+        // A single chunk is left as is
+        let msg_text = mtb().plain("https://http.cat").build();
+        let chunks: Vec<_> = split_chunks(&msg_text).collect();
+        assert_eq!(chunks, [msg_text]);
     }
 }
@@ -2,6 +2,8 @@ use teloxide::{prelude::*, types::Me};
 
 use crate::{button::*, text::*};
 
+// Boxing the callback query would break matching on the public variant
+#[allow(clippy::large_enum_variant)]
 pub enum RequestKind<C> {
     NewMessage(Message),
     EditedMessage(Message),
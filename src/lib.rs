@@ -1,3 +1,6 @@
+// Lets `#[derive(Args)]` refer to the crate by name from inside it too
+extern crate self as tgbot_utils;
+
 pub mod button;
 pub mod cmd_arg;
 pub mod error;
@@ -30,9 +30,8 @@ impl MediaKind {
             Video(m) => &m.video.file,
             VideoNote(m) => &m.video_note.file,
             Voice(m) => &m.voice.file,
-            Contact(_) | Game(_) | Venue(_) | Location(_) | Poll(_) | Text(_) | Migration(_) => {
-                return None
-            }
+            Contact(_) | Game(_) | Venue(_) | Location(_) | Poll(_) | Story(_) | Text(_)
+            | Migration(_) => return None,
         };
         Some(file)
     }
@@ -47,8 +46,8 @@ impl MediaKind {
             Text(m) => Some(&*m.text),
             Video(m) => m.caption.as_deref(),
             Voice(m) => m.caption.as_deref(),
-            Contact(_) | Venue(_) | Location(_) | Poll(_) | Sticker(_) | VideoNote(_)
-            | Migration(_) => None,
+            Contact(_) | Venue(_) | Location(_) | Poll(_) | Sticker(_) | Story(_)
+            | VideoNote(_) | Migration(_) => None,
         }
     }
 
@@ -62,8 +61,8 @@ impl MediaKind {
             Text(m) => &m.entities,
             Video(m) => &m.caption_entities,
             Voice(m) => &m.caption_entities,
-            Contact(_) | Venue(_) | Location(_) | Poll(_) | Sticker(_) | VideoNote(_)
-            | Migration(_) => return None,
+            Contact(_) | Venue(_) | Location(_) | Poll(_) | Sticker(_) | Story(_)
+            | VideoNote(_) | Migration(_) => return None,
         };
         Some(caption_entities)
     }
@@ -74,7 +73,7 @@ impl MediaKind {
             Photo(m) => m.has_media_spoiler,
             Video(m) => m.has_media_spoiler,
             Audio(_) | Contact(_) | Document(_) | Game(_) | Venue(_) | Location(_) | Poll(_)
-            | Sticker(_) | Text(_) | VideoNote(_) | Voice(_) | Migration(_) => false,
+            | Sticker(_) | Story(_) | Text(_) | VideoNote(_) | Voice(_) | Migration(_) => false,
         }
     }
 
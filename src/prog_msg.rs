@@ -1,9 +1,12 @@
 use teloxide::{
     prelude::*,
-    types::{Message, MessageId},
+    types::{LinkPreviewOptions, Message, MessageId, ReplyParameters},
 };
 
-use super::handle::{Response, ResponseKind};
+use super::{
+    handle::{Response, ResponseKind},
+    text::LinkPreview,
+};
 
 pub struct ProgMsg<'a> {
     bot: &'a Bot,
@@ -51,7 +54,7 @@ impl<'a> ProgMsg<'a> {
                 let msg = self
                     .bot
                     .send_message(self.trigger_msg.chat.id, text)
-                    .reply_to_message_id(self.trigger_msg.id)
+                    .reply_parameters(ReplyParameters::new(self.trigger_msg.id))
                     .await;
                 if let Ok(msg) = msg {
                     self.msg_id = Some(msg.id);
@@ -80,11 +83,16 @@ impl<'a> ProgMsg<'a> {
         };
 
         if let ResponseKind::ReplyTo(text, buttons) = resp.kind {
+            // Progress messages don't show a preview unless asked to
+            let link_preview: LinkPreviewOptions = match text.is_link_preview_set() {
+                true => text.link_preview().into(),
+                false => (&LinkPreview::disabled()).into(),
+            };
             let mut builder = self
                 .bot
                 .edit_message_text(self.trigger_msg.chat.id, msg_id, text.text())
-                .entities(text.into_entities())
-                .disable_web_page_preview(true);
+                .link_preview_options(link_preview)
+                .entities(text.into_entities());
             if let Some(buttons) = buttons {
                 builder = builder.reply_markup(buttons.into());
             }
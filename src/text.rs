@@ -5,7 +5,28 @@ use teloxide::types::{
 };
 use url::Url;
 
-pub fn chat_display_name(chat: &Chat) -> Cow<str> {
+mod edit;
+mod extract;
+mod link;
+mod markup;
+pub mod mention;
+mod plain;
+mod preview;
+mod slice;
+mod split;
+mod store;
+mod validate;
+
+pub use edit::TemplateError;
+pub use extract::{EntityTag, ExtractAction, ExtractOptions, Extracted, ExtractedSpan};
+pub use link::{parse_tg_link, LinkChat, MessageLink, TgLink};
+pub use markup::MarkupError;
+pub use plain::{PlainTextOptions, TextMentionStyle};
+pub use preview::{LinkPreview, PreviewMediaSize};
+pub use split::{CAPTION_LIMIT, MESSAGE_TEXT_LIMIT};
+pub use validate::EntityError;
+
+pub fn chat_display_name(chat: &Chat) -> Cow<'_, str> {
     match &chat.kind {
         ChatKind::Public(chat) => chat.title.as_deref().map(Cow::Borrowed),
         ChatKind::Private(chat) => {
@@ -40,7 +61,8 @@ pub fn user_url(user: &User) -> Option<Url> {
 pub struct MessageText<'a> {
     text: Cow<'a, str>,
     entities: Cow<'a, [MessageEntity]>,
-    disable_preview: bool,
+    // `None` until set, leaving the choice to where the text is sent
+    link_preview: Option<LinkPreview>,
 }
 
 // Shorthand
@@ -62,7 +84,7 @@ impl<'a> MessageText<'a> {
         Self {
             text: text.into(),
             entities: entities.into(),
-            disable_preview: false,
+            link_preview: None,
         }
     }
 
@@ -75,18 +97,43 @@ impl<'a> MessageText<'a> {
     }
 
     pub fn disable_preview(&self) -> bool {
-        self.disable_preview
+        self.link_preview().disabled
     }
 
     pub fn set_preview(&mut self, enabled: bool) {
-        self.disable_preview = !enabled;
+        self.link_preview_mut().disabled = !enabled;
+    }
+
+    pub fn link_preview(&self) -> &LinkPreview {
+        static DEFAULT: LinkPreview = LinkPreview::new();
+        self.link_preview.as_ref().unwrap_or(&DEFAULT)
+    }
+
+    pub fn is_link_preview_set(&self) -> bool {
+        self.link_preview.is_some()
+    }
+
+    pub fn set_link_preview(&mut self, link_preview: LinkPreview) {
+        self.link_preview = Some(link_preview);
+    }
+
+    fn link_preview_mut(&mut self) -> &mut LinkPreview {
+        self.link_preview.get_or_insert_with(Default::default)
     }
 
     pub fn into_entities(self) -> Vec<MessageEntity> {
         self.entities.into()
     }
 
-    pub fn parse_entities(&self) -> Vec<MessageEntityRef> {
+    pub fn into_owned(self) -> MessageText<'static> {
+        MessageText {
+            text: self.text.into_owned().into(),
+            entities: self.entities.into_owned().into(),
+            link_preview: self.link_preview,
+        }
+    }
+
+    pub fn parse_entities(&self) -> Vec<MessageEntityRef<'_>> {
         MessageEntityRef::parse(&self.text, &self.entities)
     }
 
@@ -97,10 +144,9 @@ impl<'a> MessageText<'a> {
     pub fn prepend_text(&mut self, text: impl AsRef<str>) {
         let text = text.as_ref();
 
-        self.entities
-            .to_mut()
-            .iter_mut()
-            .for_each(|entity| entity.offset += text.encode_utf16().count());
+        self.entities.to_mut().iter_mut().for_each(|entity| {
+            entity.offset = entity.offset.saturating_add(text.encode_utf16().count())
+        });
         self.text.to_mut().insert_str(0, text);
     }
 
@@ -144,7 +190,7 @@ impl<'a> MessageText<'a> {
         }
     }
 
-    pub fn append(&mut self, other: impl Into<Self>) {
+    pub fn append<'b>(&mut self, other: impl Into<MessageText<'b>>) {
         let mut other = other.into();
 
         let old_text_len = self.text.encode_utf16().count();
@@ -153,12 +199,27 @@ impl<'a> MessageText<'a> {
             .entities
             .to_mut()
             .iter_mut()
-            .for_each(|entity| entity.offset += old_text_len);
+            .for_each(|entity| entity.offset = entity.offset.saturating_add(old_text_len));
 
         self.entities.to_mut().append(&mut other.entities.into());
         self.text.to_mut().push_str(&other.text);
     }
 
+    pub fn append_with_entity<'b>(
+        &mut self,
+        other: impl Into<MessageText<'b>>,
+        kind: MessageEntityKind,
+    ) {
+        let other = other.into();
+
+        self.entities.to_mut().push(MessageEntity {
+            kind,
+            offset: self.text.encode_utf16().count(),
+            length: other.text.encode_utf16().count(),
+        });
+        self.append(other);
+    }
+
     pub fn prepend(&mut self, other: impl Into<Self>) {
         let mut other = other.into();
 
@@ -166,36 +227,37 @@ impl<'a> MessageText<'a> {
         other.entities.to_mut().append(self.entities.to_mut());
         self.entities = other.entities;
     }
+}
+
+// Offsets and lengths come from the outside, so their sum may overflow
+fn entity_end(entity: &MessageEntity) -> usize {
+    entity.offset.saturating_add(entity.length)
+}
 
-    pub fn extract_semantics(&self) -> String {
-        use MessageEntityKind::*;
-
-        self.parse_entities()
-            .into_iter()
-            .rev()
-            .filter(|entity| match entity.kind() {
-                Mention
-                | Hashtag
-                | Cashtag
-                | BotCommand
-                | Url
-                | Email
-                | PhoneNumber
-                | Pre { .. }
-                | CustomEmoji { .. } => true,
-                Bold
-                | Italic
-                | Underline
-                | Strikethrough
-                | Spoiler
-                | Code
-                | TextLink { .. }
-                | TextMention { .. } => false,
-            })
-            .fold(self.text().to_owned(), |mut text, entity| {
-                text.replace_range(entity.start()..entity.end(), "");
-                text
-            })
+// Entities whose meaning depends on their whole text. They are dropped instead
+// of shortened when cut, and never merged with their neighbors.
+fn is_atomic(kind: &MessageEntityKind) -> bool {
+    use MessageEntityKind::*;
+
+    match kind {
+        Mention
+        | Hashtag
+        | Cashtag
+        | BotCommand
+        | Url
+        | Email
+        | PhoneNumber
+        | CustomEmoji { .. } => true,
+        Bold
+        | Blockquote
+        | Italic
+        | Underline
+        | Strikethrough
+        | Spoiler
+        | Code
+        | Pre { .. }
+        | TextLink { .. }
+        | TextMention { .. } => false,
     }
 }
 
@@ -211,6 +273,24 @@ impl<'a> From<String> for MessageText<'a> {
     }
 }
 
+impl<'a> From<&'a String> for MessageText<'a> {
+    fn from(value: &'a String) -> Self {
+        Self::new(value.as_str(), vec![])
+    }
+}
+
+impl<'a> From<Cow<'a, str>> for MessageText<'a> {
+    fn from(value: Cow<'a, str>) -> Self {
+        Self::new(value, vec![])
+    }
+}
+
+impl<'a> From<MessageTextBuilder<'a>> for MessageText<'a> {
+    fn from(value: MessageTextBuilder<'a>) -> Self {
+        value.build()
+    }
+}
+
 impl<'a> Add for MessageText<'a> {
     type Output = Self;
 
@@ -220,12 +300,52 @@ impl<'a> Add for MessageText<'a> {
     }
 }
 
-// TODO: overlapping support
 pub struct MessageTextBuilder<'a> {
     text: MessageText<'a>,
 }
 
+// What an entity can wrap: plain text, another `MessageText`, a nested
+// builder, or a closure returning one, e.g. `.bold(|| mtb().plain("x"))`
+pub trait EntityContent<'b> {
+    fn into_message_text(self) -> MessageText<'b>;
+}
+
+macro_rules! impl_entity_content {
+    ( $( $ty:ty ),+ $(,)? ) => {
+        $(impl<'b> EntityContent<'b> for $ty {
+            fn into_message_text(self) -> MessageText<'b> {
+                self.into()
+            }
+        })+
+    };
+}
+
+impl_entity_content!(
+    &'b str,
+    String,
+    &'b String,
+    Cow<'b, str>,
+    MessageText<'b>,
+    MessageTextBuilder<'b>,
+);
+
+impl<'b, F: FnOnce() -> MessageTextBuilder<'b>> EntityContent<'b> for F {
+    fn into_message_text(self) -> MessageText<'b> {
+        self().build()
+    }
+}
+
 macro_rules! define_entity_methods {
+    ( $( $name:ident => $kind:ident ),+ $(,)? ) => {
+        $(pub fn $name<'b>(mut self, content: impl EntityContent<'b>) -> Self {
+            self.text
+                .append_with_entity(content.into_message_text(), MessageEntityKind::$kind);
+            self
+        })+
+    };
+}
+
+macro_rules! define_plain_entity_methods {
     ( $( $name:ident => $kind:ident ),+ $(,)? ) => {
         $(pub fn $name(mut self, text: impl AsRef<str>) -> Self {
             self.text
@@ -241,8 +361,16 @@ impl<'a> MessageTextBuilder<'a> {
         self
     }
 
-    pub fn link(mut self, text: impl AsRef<str>, url: Url) -> Self {
-        self.text.append_text_link(text, url);
+    pub fn append<'b>(mut self, text: impl Into<MessageText<'b>>) -> Self {
+        self.text.append(text);
+        self
+    }
+
+    pub fn link<'b>(mut self, content: impl EntityContent<'b>, url: Url) -> Self {
+        self.text.append_with_entity(
+            content.into_message_text(),
+            MessageEntityKind::TextLink { url },
+        );
         self
     }
 
@@ -252,7 +380,19 @@ impl<'a> MessageTextBuilder<'a> {
         underline => Underline,
         strikethrough => Strikethrough,
         spoiler => Spoiler,
+    }
+
+    // Telegram doesn't allow other entities inside code and pre, and the other
+    // ones are only meaningful for their whole text
+    define_plain_entity_methods! {
         code => Code,
+        mention => Mention,
+        hashtag => Hashtag,
+        cashtag => Cashtag,
+        bot_command => BotCommand,
+        url => Url,
+        email => Email,
+        phone_number => PhoneNumber,
     }
 
     pub fn pre(mut self, text: impl AsRef<str>) -> Self {
@@ -261,13 +401,73 @@ impl<'a> MessageTextBuilder<'a> {
         self
     }
 
+    pub fn pre_lang(mut self, text: impl AsRef<str>, language: impl Into<String>) -> Self {
+        self.text.append_text_with_entity(
+            text,
+            MessageEntityKind::Pre {
+                language: Some(language.into()),
+            },
+        );
+        self
+    }
+
+    pub fn text_mention<'b>(mut self, content: impl EntityContent<'b>, user: &User) -> Self {
+        self.text.append_with_entity(
+            content.into_message_text(),
+            MessageEntityKind::TextMention { user: user.clone() },
+        );
+        self
+    }
+
+    pub fn custom_emoji(mut self, text: impl AsRef<str>, id: impl Into<String>) -> Self {
+        self.text.append_text_with_entity(
+            text,
+            MessageEntityKind::CustomEmoji {
+                custom_emoji_id: id.into(),
+            },
+        );
+        self
+    }
+
+    pub fn user_link(mut self, user: &User) -> Self {
+        self.text
+            .append_text_link_fallback(user.full_name(), user_url(user));
+        self
+    }
+
+    pub fn chat_link(mut self, chat: &Chat, msg_id: MessageId) -> Self {
+        self.text
+            .append_text_link_fallback(chat_display_name(chat), message_url(chat, msg_id));
+        self
+    }
+
     pub fn preview(mut self, enable: bool) -> Self {
-        self.text.disable_preview = !enable;
+        self.text.set_preview(enable);
         self
     }
 
     pub fn disable_preview(mut self) -> Self {
-        self.text.disable_preview = true;
+        self.text.set_preview(false);
+        self
+    }
+
+    pub fn link_preview(mut self, link_preview: LinkPreview) -> Self {
+        self.text.set_link_preview(link_preview);
+        self
+    }
+
+    pub fn preview_url(mut self, url: Url) -> Self {
+        self.text.link_preview_mut().url = Some(url);
+        self
+    }
+
+    pub fn preview_media_size(mut self, size: PreviewMediaSize) -> Self {
+        self.text.link_preview_mut().media_size = Some(size);
+        self
+    }
+
+    pub fn preview_above_text(mut self) -> Self {
+        self.text.link_preview_mut().above_text = true;
         self
     }
 
@@ -278,6 +478,9 @@ impl<'a> MessageTextBuilder<'a> {
 
 #[cfg(test)]
 mod tests {
+    use serde_json::{self as json, json};
+    use teloxide::types::UserId;
+
     use super::*;
 
     #[test]
@@ -498,4 +701,211 @@ mod tests {
             "meow 🍓 link 🐟 cute  🐱 喵呜"
         );
     }
+
+    #[test]
+    fn builder_link_preview() {
+        let url: Url = "https://http.cat".try_into().unwrap();
+
+        let msg_text = mtb()
+            .plain("meow")
+            .preview_url(url.clone())
+            .preview_media_size(PreviewMediaSize::Small)
+            .preview_above_text()
+            .build();
+        assert!(!msg_text.disable_preview());
+        assert_eq!(
+            msg_text.link_preview(),
+            &LinkPreview::url(url)
+                .media_size(PreviewMediaSize::Small)
+                .above_text(true)
+        );
+
+        let mut msg_text = mtb().plain("meow").disable_preview().build();
+        assert_eq!(msg_text.link_preview(), &LinkPreview::disabled());
+        msg_text.set_preview(true);
+        assert_eq!(msg_text.link_preview(), &LinkPreview::default());
+        assert!(msg_text.is_link_preview_set());
+
+        let msg_text = mtb().plain("meow").build();
+        assert!(!msg_text.is_link_preview_set());
+        assert_eq!(msg_text.link_preview(), &LinkPreview::default());
+        assert!(mtb().preview(true).build().is_link_preview_set());
+    }
+
+    #[test]
+    fn builder_nested() {
+        let url: Url = "https://http.cat".try_into().unwrap();
+
+        let msg_text = mtb()
+            .plain("🐱 ")
+            .bold(|| mtb().plain("bold ").link("link", url.clone()))
+            .plain(" ")
+            .italic(mtb().plain("喵 ").code("code"))
+            .link(mtb().underline("u").plain("l"), url.clone())
+            .build();
+
+        assert_eq!(msg_text.text(), "🐱 bold link 喵 codeul");
+        assert_eq!(
+            msg_text.entities(),
+            vec![
+                MessageEntity {
+                    kind: MessageEntityKind::Bold,
+                    offset: 3,
+                    length: 9,
+                },
+                MessageEntity {
+                    kind: MessageEntityKind::TextLink { url: url.clone() },
+                    offset: 8,
+                    length: 4,
+                },
+                MessageEntity {
+                    kind: MessageEntityKind::Italic,
+                    offset: 13,
+                    length: 6,
+                },
+                MessageEntity {
+                    kind: MessageEntityKind::Code,
+                    offset: 15,
+                    length: 4,
+                },
+                MessageEntity {
+                    kind: MessageEntityKind::TextLink { url },
+                    offset: 19,
+                    length: 2,
+                },
+                MessageEntity {
+                    kind: MessageEntityKind::Underline,
+                    offset: 19,
+                    length: 1,
+                },
+            ]
+        );
+        assert_eq!(
+            msg_text.to_html(),
+            "🐱 <b>bold <a href=\"https://http.cat/\">link</a></b> <i>喵 <code>code</code></i>\
+             <a href=\"https://http.cat/\"><u>u</u>l</a>"
+        );
+    }
+
+    #[test]
+    fn builder_all_kinds() {
+        let user = User {
+            id: UserId(42),
+            is_bot: false,
+            first_name: "Cat".into(),
+            last_name: None,
+            username: Some("cat".into()),
+            language_code: None,
+            is_premium: false,
+            added_to_attachment_menu: false,
+        };
+
+        let msg_text = mtb()
+            .mention("@cat")
+            .hashtag("#cat")
+            .cashtag("$CAT")
+            .bot_command("/meow")
+            .url("https://http.cat")
+            .email("cat@http.cat")
+            .phone_number("+1")
+            .pre_lang("meow!()", "rust")
+            .text_mention(mtb().bold("Cat"), &user)
+            .custom_emoji("🐱", "5368324170671202286")
+            .user_link(&user)
+            .build();
+
+        assert_eq!(
+            msg_text.text(),
+            "@cat#cat$CAT/meowhttps://http.catcat@http.cat+1meow!()Cat🐱Cat"
+        );
+        assert_eq!(
+            msg_text
+                .entities()
+                .iter()
+                .map(|entity| (entity.kind.clone(), entity.offset, entity.length))
+                .collect::<Vec<_>>(),
+            vec![
+                (MessageEntityKind::Mention, 0, 4),
+                (MessageEntityKind::Hashtag, 4, 4),
+                (MessageEntityKind::Cashtag, 8, 4),
+                (MessageEntityKind::BotCommand, 12, 5),
+                (MessageEntityKind::Url, 17, 16),
+                (MessageEntityKind::Email, 33, 12),
+                (MessageEntityKind::PhoneNumber, 45, 2),
+                (
+                    MessageEntityKind::Pre {
+                        language: Some("rust".into())
+                    },
+                    47,
+                    7
+                ),
+                (MessageEntityKind::TextMention { user: user.clone() }, 54, 3),
+                (MessageEntityKind::Bold, 54, 3),
+                (
+                    MessageEntityKind::CustomEmoji {
+                        custom_emoji_id: "5368324170671202286".into()
+                    },
+                    57,
+                    2
+                ),
+                (
+                    MessageEntityKind::TextLink {
+                        url: "https://t.me/cat".try_into().unwrap()
+                    },
+                    59,
+                    3
+                ),
+            ]
+        );
+
+        let user = User {
+            username: None,
+            ..user
+        };
+        let msg_text = mtb().user_link(&user).build();
+        assert_eq!(msg_text.text(), "Cat");
+        assert!(msg_text.entities().is_empty());
+    }
+
+    #[test]
+    fn builder_chat_link() {
+        let chat = |username: Option<&str>| -> Chat {
+            json::from_value(json!({
+                "id": -1001234567890i64,
+                "type": "supergroup",
+                "title": "Cats",
+                "username": username,
+            }))
+            .unwrap()
+        };
+
+        let msg_text = mtb()
+            .plain("in ")
+            .chat_link(&chat(Some("cats")), MessageId(42))
+            .build();
+        assert_eq!(msg_text.text(), "in Cats");
+        assert_eq!(
+            msg_text.entities(),
+            vec![MessageEntity {
+                kind: MessageEntityKind::TextLink {
+                    url: "https://t.me/cats/42".try_into().unwrap()
+                },
+                offset: 3,
+                length: 4,
+            }]
+        );
+
+        let msg_text = mtb().chat_link(&chat(None), MessageId(42)).build();
+        assert_eq!(msg_text.text(), "Cats");
+        assert_eq!(
+            msg_text.entities(),
+            vec![MessageEntity {
+                kind: MessageEntityKind::TextLink {
+                    url: "https://t.me/c/1234567890/42".try_into().unwrap()
+                },
+                offset: 0,
+                length: 4,
+            }]
+        );
+    }
 }
@@ -0,0 +1,354 @@
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    ops::{Range, RangeBounds},
+};
+
+use teloxide::types::{MessageEntity, MessageEntityKind};
+
+use super::{entity_end, is_atomic, MessageText};
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum TemplateError {
+    #[error("no value for placeholder `{0}`")]
+    MissingValue(String),
+
+    #[error("unclosed placeholder at byte {position}")]
+    Unclosed { position: usize },
+
+    #[error("invalid placeholder name `{name}` at byte {position}")]
+    InvalidName { name: String, position: usize },
+
+    #[error("unmatched `}}` at byte {position}, use `}}}}` for a literal one")]
+    UnmatchedBrace { position: usize },
+}
+
+impl<'a> MessageText<'a> {
+    // `at` is in UTF-16 code units, like all the editing methods below.
+    pub fn insert<'b>(&mut self, at: usize, text: impl Into<MessageText<'b>>) {
+        let (bytes, units) = self.resolve_range(at..at);
+        self.splice(bytes, units, text.into());
+    }
+
+    pub fn remove(&mut self, range: impl RangeBounds<usize>) {
+        let (bytes, units) = self.resolve_range(range);
+        self.splice(bytes, units, MessageText::from(""));
+    }
+
+    pub fn replace_range<'b>(
+        &mut self,
+        range: impl RangeBounds<usize>,
+        replacement: impl Into<MessageText<'b>>,
+    ) {
+        let (bytes, units) = self.resolve_range(range);
+        self.splice(bytes, units, replacement.into());
+    }
+
+    // Replaces all occurrences of `pattern`, returning how many were replaced.
+    pub fn replace<'b>(
+        &mut self,
+        pattern: impl AsRef<str>,
+        replacement: impl Into<MessageText<'b>>,
+    ) -> usize {
+        let pattern = pattern.as_ref();
+        if pattern.is_empty() {
+            return 0;
+        }
+        let replacement = replacement.into();
+
+        let matches = self.byte_ranges_to_units(
+            self.text
+                .match_indices(pattern)
+                .map(|(index, matched)| index..index + matched.len()),
+        );
+        let count = matches.len();
+        for (bytes, units) in matches.into_iter().rev() {
+            self.splice(bytes, units, replacement.clone());
+        }
+        count
+    }
+
+    // Fills `{name}` placeholders with the given values, which may be formatted
+    // themselves. Literal braces are written as `{{` and `}}`.
+    pub fn template<'b, 'k>(
+        &self,
+        values: impl IntoIterator<Item = (&'k str, MessageText<'b>)>,
+    ) -> Result<MessageText<'static>, TemplateError> {
+        let values: HashMap<_, _> = values.into_iter().collect();
+
+        let mut replacements = vec![];
+        let mut chars = self.text.char_indices().peekable();
+        while let Some((index, ch)) = chars.next() {
+            match ch {
+                '{' if chars.next_if(|&(_, ch)| ch == '{').is_some() => {
+                    replacements.push((index..index + 2, MessageText::from("{")));
+                }
+                '}' if chars.next_if(|&(_, ch)| ch == '}').is_some() => {
+                    replacements.push((index..index + 2, MessageText::from("}")));
+                }
+                '{' => {
+                    let end = chars
+                        .find(|&(_, ch)| ch == '}')
+                        .ok_or(TemplateError::Unclosed { position: index })?
+                        .0;
+                    let name = &self.text[index + 1..end];
+                    if name.is_empty()
+                        || !name
+                            .chars()
+                            .all(|ch| ch.is_ascii_alphanumeric() || ch == '_')
+                    {
+                        return Err(TemplateError::InvalidName {
+                            name: name.into(),
+                            position: index,
+                        });
+                    }
+                    let value = values
+                        .get(name)
+                        .ok_or_else(|| TemplateError::MissingValue(name.into()))?;
+                    replacements.push((index..end + 1, value.clone()));
+                }
+                '}' => return Err(TemplateError::UnmatchedBrace { position: index }),
+                _ => {}
+            }
+        }
+
+        let mut result = self.slice(..);
+        let ranges =
+            result.byte_ranges_to_units(replacements.iter().map(|(bytes, _)| bytes.clone()));
+        for ((bytes, units), (_, value)) in ranges.into_iter().zip(replacements).rev() {
+            result.splice(bytes, units, value);
+        }
+        Ok(result)
+    }
+
+    // Converts sorted, non-overlapping byte ranges into (bytes, UTF-16) pairs
+    fn byte_ranges_to_units(
+        &self,
+        ranges: impl IntoIterator<Item = Range<usize>>,
+    ) -> Vec<(Range<usize>, Range<usize>)> {
+        let (mut index, mut offset) = (0, 0);
+        let mut to_units = |target: usize| {
+            offset += self.text[index..target].encode_utf16().count();
+            index = target;
+            offset
+        };
+
+        ranges
+            .into_iter()
+            .map(|bytes| {
+                let units = to_units(bytes.start)..to_units(bytes.end);
+                (bytes, units)
+            })
+            .collect()
+    }
+
+    // Replaces a char-aligned range with another text.
+    //
+    // Entities around the range grow or shrink, the ones after it are shifted,
+    // and the ones partially covering it are clipped. A code or pre entity around
+    // the range is split instead if the replacement is formatted, as they can't
+    // contain other entities. Atomic entities are dropped once modified.
+    fn splice(&mut self, bytes: Range<usize>, units: Range<usize>, replacement: MessageText) {
+        let Range { start, end } = units;
+        let replacement_len = replacement.text.encode_utf16().count();
+        let shift = |offset: usize| offset.saturating_add(replacement_len) - (end - start);
+
+        let mut entities = Vec::with_capacity(self.entities.len() + replacement.entities.len());
+        for entity in self.entities.iter() {
+            let entity_end = entity_end(entity);
+            let contains = if start == end {
+                entity.offset < start && start < entity_end
+            } else {
+                entity.offset <= start && end <= entity_end
+            };
+            let is_code = matches!(
+                entity.kind,
+                MessageEntityKind::Code | MessageEntityKind::Pre { .. }
+            );
+
+            let parts = if entity_end <= start {
+                vec![(entity.offset, entity_end)]
+            } else if entity.offset >= end {
+                vec![(shift(entity.offset), shift(entity_end))]
+            } else if is_atomic(&entity.kind) {
+                vec![]
+            } else if contains && is_code && !replacement.entities.is_empty() {
+                vec![
+                    (entity.offset, start),
+                    (start + replacement_len, shift(entity_end)),
+                ]
+            } else if contains {
+                vec![(entity.offset, shift(entity_end))]
+            } else if entity.offset >= start && entity_end <= end {
+                vec![]
+            } else if entity.offset < start {
+                vec![(entity.offset, start)]
+            } else {
+                vec![(start + replacement_len, shift(entity_end))]
+            };
+
+            entities.extend(parts.into_iter().filter(|(start, end)| start < end).map(
+                |(start, end)| MessageEntity {
+                    kind: entity.kind.clone(),
+                    offset: start,
+                    length: end - start,
+                },
+            ));
+        }
+        entities.extend(replacement.entities.iter().map(|entity| MessageEntity {
+            kind: entity.kind.clone(),
+            offset: entity.offset.saturating_add(start),
+            length: entity.length,
+        }));
+        entities.sort_by_key(|entity| (entity.offset, Reverse(entity.length)));
+
+        self.text.to_mut().replace_range(bytes, &replacement.text);
+        self.entities = entities.into();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text::mtb;
+
+    fn entity(kind: MessageEntityKind, offset: usize, length: usize) -> MessageEntity {
+        MessageEntity {
+            kind,
+            offset,
+            length,
+        }
+    }
+
+    #[test]
+    fn insert_and_remove() {
+        let mut msg_text = mtb().plain("🐱 ").bold("meow").plain(" 喵").build();
+
+        msg_text.insert(5, mtb().italic("~"));
+        assert_eq!(msg_text.text(), "🐱 me~ow 喵");
+        assert_eq!(
+            msg_text.entities(),
+            vec![
+                entity(MessageEntityKind::Bold, 3, 5),
+                entity(MessageEntityKind::Italic, 5, 1),
+            ]
+        );
+
+        // Inserting at the edges doesn't extend the entity
+        msg_text.insert(3, "<");
+        msg_text.insert(9, ">");
+        assert_eq!(msg_text.text(), "🐱 <me~ow> 喵");
+        assert_eq!(
+            msg_text.entities(),
+            vec![
+                entity(MessageEntityKind::Bold, 4, 5),
+                entity(MessageEntityKind::Italic, 6, 1),
+            ]
+        );
+
+        msg_text.remove(6..7);
+        assert_eq!(msg_text.text(), "🐱 <meow> 喵");
+        assert_eq!(
+            msg_text.entities(),
+            vec![entity(MessageEntityKind::Bold, 4, 4)]
+        );
+
+        // Partially covered entities are clipped
+        msg_text.remove(1..6);
+        assert_eq!(msg_text.text(), "🐱ow> 喵");
+        assert_eq!(
+            msg_text.entities(),
+            vec![entity(MessageEntityKind::Bold, 2, 2)]
+        );
+    }
+
+    #[test]
+    fn replace() {
+        let mut msg_text = mtb()
+            .bold("Hi {user}!")
+            .plain(" ")
+            .code("let {user} = 1;")
+            .build();
+        msg_text.append_text_with_entity(" @{user}", MessageEntityKind::Mention);
+
+        let count = msg_text.replace(
+            "{user}",
+            mtb().link("Cat", "https://http.cat".try_into().unwrap()),
+        );
+        assert_eq!(count, 3);
+        assert_eq!(msg_text.text(), "Hi Cat! let Cat = 1; @Cat");
+        assert_eq!(
+            msg_text.entities(),
+            vec![
+                entity(MessageEntityKind::Bold, 0, 7),
+                entity(
+                    MessageEntityKind::TextLink {
+                        url: "https://http.cat".try_into().unwrap()
+                    },
+                    3,
+                    3
+                ),
+                entity(MessageEntityKind::Code, 8, 4),
+                entity(
+                    MessageEntityKind::TextLink {
+                        url: "https://http.cat".try_into().unwrap()
+                    },
+                    12,
+                    3
+                ),
+                entity(MessageEntityKind::Code, 15, 5),
+                entity(
+                    MessageEntityKind::TextLink {
+                        url: "https://http.cat".try_into().unwrap()
+                    },
+                    22,
+                    3
+                ),
+            ]
+        );
+
+        assert_eq!(msg_text.replace("", "x"), 0);
+        assert_eq!(msg_text.replace("dog", "x"), 0);
+    }
+
+    #[test]
+    fn template() {
+        let template =
+            MessageText::from_html("<b>{user}</b> has {count} new messages {{in 🐱}}").unwrap();
+
+        let msg_text = template
+            .template([
+                ("user", mtb().italic("Cat").build()),
+                ("count", "42".into()),
+            ])
+            .unwrap();
+        assert_eq!(msg_text.text(), "Cat has 42 new messages {in 🐱}");
+        assert_eq!(
+            msg_text.entities(),
+            vec![
+                entity(MessageEntityKind::Bold, 0, 3),
+                entity(MessageEntityKind::Italic, 0, 3),
+            ]
+        );
+
+        assert_eq!(
+            template.template([("user", "Cat".into())]),
+            Err(TemplateError::MissingValue("count".into()))
+        );
+        assert_eq!(
+            MessageText::from("{user").template([]),
+            Err(TemplateError::Unclosed { position: 0 })
+        );
+        assert_eq!(
+            MessageText::from("{a b}").template([]),
+            Err(TemplateError::InvalidName {
+                name: "a b".into(),
+                position: 0
+            })
+        );
+        assert_eq!(
+            MessageText::from("a}").template([]),
+            Err(TemplateError::UnmatchedBrace { position: 1 })
+        );
+    }
+}
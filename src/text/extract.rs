@@ -0,0 +1,370 @@
+use std::{cmp::Reverse, collections::HashMap, ops::Range};
+
+use teloxide::types::MessageEntityKind;
+
+use super::{entity_end, MessageText};
+
+// The kind of an entity without its payload
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum EntityTag {
+    Mention,
+    Hashtag,
+    Cashtag,
+    BotCommand,
+    Url,
+    Email,
+    PhoneNumber,
+    Bold,
+    Blockquote,
+    Italic,
+    Underline,
+    Strikethrough,
+    Spoiler,
+    Code,
+    Pre,
+    TextLink,
+    TextMention,
+    CustomEmoji,
+}
+
+impl From<&MessageEntityKind> for EntityTag {
+    fn from(kind: &MessageEntityKind) -> Self {
+        match kind {
+            MessageEntityKind::Mention => Self::Mention,
+            MessageEntityKind::Hashtag => Self::Hashtag,
+            MessageEntityKind::Cashtag => Self::Cashtag,
+            MessageEntityKind::BotCommand => Self::BotCommand,
+            MessageEntityKind::Url => Self::Url,
+            MessageEntityKind::Email => Self::Email,
+            MessageEntityKind::PhoneNumber => Self::PhoneNumber,
+            MessageEntityKind::Bold => Self::Bold,
+            MessageEntityKind::Blockquote => Self::Blockquote,
+            MessageEntityKind::Italic => Self::Italic,
+            MessageEntityKind::Underline => Self::Underline,
+            MessageEntityKind::Strikethrough => Self::Strikethrough,
+            MessageEntityKind::Spoiler => Self::Spoiler,
+            MessageEntityKind::Code => Self::Code,
+            MessageEntityKind::Pre { .. } => Self::Pre,
+            MessageEntityKind::TextLink { .. } => Self::TextLink,
+            MessageEntityKind::TextMention { .. } => Self::TextMention,
+            MessageEntityKind::CustomEmoji { .. } => Self::CustomEmoji,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ExtractAction {
+    Keep,
+    Strip,
+    // Replaces the whole entity with a placeholder, e.g. `<url>`
+    Replace(String),
+}
+
+// Chooses what happens to the text of each entity kind when extracting. Kinds
+// without an action are kept.
+#[derive(Clone, Debug)]
+pub struct ExtractOptions {
+    actions: HashMap<EntityTag, ExtractAction>,
+}
+
+impl Default for ExtractOptions {
+    // Strips everything that isn't prose, like `extract_semantics` does
+    fn default() -> Self {
+        use EntityTag::*;
+
+        let actions = [
+            Mention,
+            Hashtag,
+            Cashtag,
+            BotCommand,
+            Url,
+            Email,
+            PhoneNumber,
+            Pre,
+            CustomEmoji,
+        ]
+        .into_iter()
+        .map(|tag| (tag, ExtractAction::Strip))
+        .collect();
+        Self { actions }
+    }
+}
+
+impl ExtractOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn keep_all() -> Self {
+        Self {
+            actions: HashMap::new(),
+        }
+    }
+
+    pub fn keep(self, tag: EntityTag) -> Self {
+        self.action(tag, ExtractAction::Keep)
+    }
+
+    pub fn strip(self, tag: EntityTag) -> Self {
+        self.action(tag, ExtractAction::Strip)
+    }
+
+    pub fn replace(self, tag: EntityTag, placeholder: impl Into<String>) -> Self {
+        self.action(tag, ExtractAction::Replace(placeholder.into()))
+    }
+
+    pub fn action(mut self, tag: EntityTag, action: ExtractAction) -> Self {
+        self.actions.insert(tag, action);
+        self
+    }
+
+    fn action_of(&self, kind: &MessageEntityKind) -> &ExtractAction {
+        self.actions
+            .get(&EntityTag::from(kind))
+            .unwrap_or(&ExtractAction::Keep)
+    }
+}
+
+// A run of the extracted text and where it came from. `output` is in bytes of
+// the extracted text, `original` in UTF-16 code units of the original one.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExtractedSpan {
+    pub output: Range<usize>,
+    pub original: Range<usize>,
+    // Whether `output` is a placeholder for the whole `original` range
+    pub replaced: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Extracted {
+    text: String,
+    spans: Vec<ExtractedSpan>,
+}
+
+impl Extracted {
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn into_text(self) -> String {
+        self.text
+    }
+
+    pub fn spans(&self) -> &[ExtractedSpan] {
+        &self.spans
+    }
+
+    // Maps a byte range of the extracted text, e.g. a match of `str::find`, to
+    // the UTF-16 range of the original text it came from. The bounds must lie
+    // on char boundaries. Ranges touching a placeholder cover its whole entity.
+    pub fn original_range(&self, output: Range<usize>) -> Range<usize> {
+        let start = self.original_offset(output.start, false);
+        let end = self.original_offset(output.end, true);
+        start..end.max(start)
+    }
+
+    fn original_offset(&self, index: usize, is_end: bool) -> usize {
+        let span = self.spans.iter().find(|span| {
+            if is_end {
+                span.output.start < index && index <= span.output.end
+            } else {
+                span.output.start <= index && index < span.output.end
+            }
+        });
+
+        match span {
+            Some(span) if span.replaced => {
+                if is_end {
+                    span.original.end
+                } else {
+                    span.original.start
+                }
+            }
+            Some(span) => {
+                span.original.start + self.text[span.output.start..index].encode_utf16().count()
+            }
+            // Empty ranges at the very start or the end of the text
+            None => self
+                .spans
+                .iter()
+                .rev()
+                .find(|span| span.output.end <= index)
+                .map_or(0, |span| span.original.end),
+        }
+    }
+}
+
+impl<'a> MessageText<'a> {
+    pub fn extract_semantics(&self) -> String {
+        self.extract(&ExtractOptions::default()).into_text()
+    }
+
+    // Nested entities inside a stripped or replaced one are ignored, and
+    // overlapping ones are merged, taking the action of the first one.
+    pub fn extract(&self, options: &ExtractOptions) -> Extracted {
+        let mut cuts: Vec<(usize, usize, &ExtractAction)> = self
+            .entities
+            .iter()
+            .filter_map(|entity| {
+                let action = options.action_of(&entity.kind);
+                (*action != ExtractAction::Keep && entity.length != 0).then_some((
+                    entity.offset,
+                    entity_end(entity),
+                    action,
+                ))
+            })
+            .collect();
+        cuts.sort_by_key(|&(start, end, _)| (start, Reverse(end)));
+
+        let mut merged: Vec<(usize, usize, &ExtractAction)> = vec![];
+        for (start, end, action) in cuts {
+            match merged.last_mut() {
+                Some(last) if start < last.1 => last.1 = last.1.max(end),
+                _ => merged.push((start, end, action)),
+            }
+        }
+
+        let mut extracted = Extracted {
+            text: String::with_capacity(self.text.len()),
+            spans: vec![],
+        };
+        let mut push = |text: &str, original: Range<usize>, replaced: bool| {
+            let start = extracted.text.len();
+            extracted.text.push_str(text);
+            if !text.is_empty() {
+                extracted.spans.push(ExtractedSpan {
+                    output: start..extracted.text.len(),
+                    original,
+                    replaced,
+                });
+            }
+        };
+
+        let (mut index, mut offset) = (0, 0);
+        for (start, end, action) in merged {
+            let (bytes, units) = self.resolve_range(start..end);
+            push(&self.text[index..bytes.start], offset..units.start, false);
+            if let ExtractAction::Replace(placeholder) = action {
+                push(placeholder, units.clone(), true);
+            }
+            (index, offset) = (bytes.end, units.end);
+        }
+        let text_len = offset + self.text[index..].encode_utf16().count();
+        push(&self.text[index..], offset..text_len, false);
+
+        extracted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text::mtb;
+
+    fn fixture() -> MessageText<'static> {
+        let mut msg_text = mtb().plain("🐱 ").bold("meow").plain(" see ").build();
+        msg_text.append_text_with_entity("https://http.cat", MessageEntityKind::Url);
+        msg_text.append_text(" 喵 ");
+        msg_text.append_text_with_entity("#cats", MessageEntityKind::Hashtag);
+        msg_text
+    }
+
+    #[test]
+    fn extract() {
+        let msg_text = fixture();
+        assert_eq!(msg_text.text(), "🐱 meow see https://http.cat 喵 #cats");
+
+        let extracted = msg_text.extract(&ExtractOptions::default());
+        assert_eq!(extracted.text(), "🐱 meow see  喵 ");
+        assert_eq!(extracted.text(), msg_text.extract_semantics());
+
+        let extracted = msg_text.extract(
+            &ExtractOptions::new()
+                .replace(EntityTag::Url, "<url>")
+                .keep(EntityTag::Hashtag)
+                .strip(EntityTag::Bold),
+        );
+        assert_eq!(extracted.text(), "🐱  see <url> 喵 #cats");
+        assert_eq!(
+            extracted.spans(),
+            vec![
+                ExtractedSpan {
+                    output: 0..5,
+                    original: 0..3,
+                    replaced: false,
+                },
+                ExtractedSpan {
+                    output: 5..10,
+                    original: 7..12,
+                    replaced: false,
+                },
+                ExtractedSpan {
+                    output: 10..15,
+                    original: 12..28,
+                    replaced: true,
+                },
+                ExtractedSpan {
+                    output: 15..25,
+                    original: 28..36,
+                    replaced: false,
+                },
+            ]
+        );
+
+        assert_eq!(
+            ExtractOptions::keep_all().action_of(&MessageEntityKind::Url),
+            &ExtractAction::Keep
+        );
+        assert_eq!(
+            msg_text.extract(&ExtractOptions::keep_all()).text(),
+            msg_text.text()
+        );
+    }
+
+    #[test]
+    fn original_range() {
+        let msg_text = fixture();
+        let extracted = msg_text.extract(&ExtractOptions::new().replace(EntityTag::Url, "<url>"));
+        assert_eq!(extracted.text(), "🐱 meow see <url> 喵 ");
+
+        let hit = extracted.text().find("meow").unwrap();
+        let range = extracted.original_range(hit..hit + "meow".len());
+        assert_eq!(range, 3..7);
+        assert_eq!(msg_text.slice(range).text(), "meow");
+
+        let hit = extracted.text().find("喵").unwrap();
+        assert_eq!(
+            msg_text
+                .slice(extracted.original_range(hit..hit + 3))
+                .text(),
+            "喵"
+        );
+
+        // Placeholders map to their whole entity
+        let hit = extracted.text().find("url").unwrap();
+        assert_eq!(
+            msg_text
+                .slice(extracted.original_range(hit..hit + 3))
+                .text(),
+            "https://http.cat"
+        );
+        let hit = extracted.text().find("see <").unwrap();
+        assert_eq!(
+            msg_text
+                .slice(extracted.original_range(hit..hit + 5))
+                .text(),
+            "see https://http.cat"
+        );
+
+        // Empty ranges and the end of the text
+        assert_eq!(extracted.original_range(0..0), 0..0);
+        let len = extracted.text().len();
+        assert_eq!(extracted.original_range(len..len), 31..31);
+        assert_eq!(
+            MessageText::from("")
+                .extract(&ExtractOptions::default())
+                .original_range(0..0),
+            0..0
+        );
+    }
+}
@@ -0,0 +1,510 @@
+use teloxide::types::{Chat, ChatId, Message, MessageId, ThreadId, UserId};
+use url::Url;
+
+// `t.me/c/` links use the id without the `-100` prefix of supergroups and
+// channels
+const MAX_MARKED_CHANNEL_ID: i64 = -1_000_000_000_000;
+const MAX_BARE_CHANNEL_ID: i64 = 997_852_516_352;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LinkChat {
+    Username(String),
+    // Private supergroups and channels, only accessible to their members
+    Id(ChatId),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MessageLink {
+    chat: LinkChat,
+    msg_id: MessageId,
+    topic_id: Option<ThreadId>,
+    thread_id: Option<ThreadId>,
+    comment_id: Option<MessageId>,
+    single: bool,
+}
+
+impl MessageLink {
+    pub fn new(chat: LinkChat, msg_id: MessageId) -> Self {
+        Self {
+            chat,
+            msg_id,
+            topic_id: None,
+            thread_id: None,
+            comment_id: None,
+            single: false,
+        }
+    }
+
+    // Messages in private chats and basic groups can't be linked
+    pub fn of_chat(chat: &Chat, msg_id: MessageId) -> Option<Self> {
+        let chat = match chat.username() {
+            Some(username) => LinkChat::Username(username.into()),
+            None if chat.id.is_channel_or_supergroup() => LinkChat::Id(chat.id),
+            None => return None,
+        };
+        Some(Self::new(chat, msg_id))
+    }
+
+    // Also links the forum topic the message is in
+    pub fn of_message(msg: &Message) -> Option<Self> {
+        let link = Self::of_chat(&msg.chat, msg.id)?;
+        Some(match msg.thread_id {
+            Some(topic_id) if msg.is_topic_message => link.topic(topic_id),
+            _ => link,
+        })
+    }
+
+    // Forum topics are linked as `t.me/c/<id>/<topic>/<msg>`
+    pub fn topic(mut self, topic_id: ThreadId) -> Self {
+        self.topic_id = Some(topic_id);
+        self
+    }
+
+    // Reply threads, e.g. the comments of a channel post in its discussion
+    // group, are linked as `?thread=<id>`
+    pub fn thread(mut self, thread_id: ThreadId) -> Self {
+        self.thread_id = Some(thread_id);
+        self
+    }
+
+    // A comment on the linked channel post
+    pub fn comment(mut self, comment_id: MessageId) -> Self {
+        self.comment_id = Some(comment_id);
+        self
+    }
+
+    // Only shows the linked item of an album
+    pub fn single(mut self) -> Self {
+        self.single = true;
+        self
+    }
+
+    pub fn chat(&self) -> &LinkChat {
+        &self.chat
+    }
+
+    pub fn msg_id(&self) -> MessageId {
+        self.msg_id
+    }
+
+    pub fn topic_id(&self) -> Option<ThreadId> {
+        self.topic_id
+    }
+
+    pub fn thread_id(&self) -> Option<ThreadId> {
+        self.thread_id
+    }
+
+    pub fn comment_id(&self) -> Option<MessageId> {
+        self.comment_id
+    }
+
+    pub fn is_single(&self) -> bool {
+        self.single
+    }
+
+    // Returns `None` if the chat is neither a supergroup nor a channel
+    pub fn to_url(&self) -> Option<Url> {
+        let mut url = match &self.chat {
+            LinkChat::Username(username) => format!("https://t.me/{username}"),
+            LinkChat::Id(chat_id) if chat_id.is_channel_or_supergroup() => {
+                format!("https://t.me/c/{}", MAX_MARKED_CHANNEL_ID - chat_id.0)
+            }
+            LinkChat::Id(_) => return None,
+        };
+        if let Some(topic_id) = self.topic_id {
+            url.push_str(&format!("/{topic_id}"));
+        }
+        url.push_str(&format!("/{}", self.msg_id));
+
+        let mut query = vec![];
+        if self.single {
+            query.push("single".into());
+        }
+        if let Some(thread_id) = self.thread_id {
+            query.push(format!("thread={thread_id}"));
+        }
+        if let Some(comment_id) = self.comment_id {
+            query.push(format!("comment={comment_id}"));
+        }
+        if !query.is_empty() {
+            url.push('?');
+            url.push_str(&query.join("&"));
+        }
+
+        Url::parse(&url).ok()
+    }
+
+    // Accepts the `t.me` forms produced by `to_url`, as well as
+    // `tg://resolve?domain=<username>&post=<msg>` and
+    // `tg://privatepost?channel=<id>&post=<msg>` ones.
+    pub fn parse(url: &Url) -> Option<Self> {
+        let query = |key: &str| {
+            url.query_pairs()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value)
+        };
+        let query_id = |key: &str| query(key).map(|value| value.parse::<i32>());
+
+        let mut link = match url.scheme() {
+            "https" | "http" if is_tme_host(url.host_str()?) => {
+                let segments: Vec<_> = url.path_segments()?.filter(|s| !s.is_empty()).collect();
+                let (chat, ids) = match segments.as_slice() {
+                    ["c", id, ids @ ..] => (LinkChat::Id(parse_bare_channel_id(id)?), ids),
+                    [username, ids @ ..] if is_username(username) => {
+                        (LinkChat::Username((*username).into()), ids)
+                    }
+                    _ => return None,
+                };
+                let (topic_id, msg_id) = match ids {
+                    [msg_id] => (None, msg_id.parse().ok()?),
+                    [topic_id, msg_id] => (Some(topic_id.parse().ok()?), msg_id.parse().ok()?),
+                    _ => return None,
+                };
+
+                let mut link = Self::new(chat, MessageId(msg_id));
+                link.topic_id = topic_id.map(|id| ThreadId(MessageId(id)));
+                link
+            }
+            "tg" => {
+                let chat = match url.host_str()? {
+                    "resolve" => {
+                        LinkChat::Username(query("domain").filter(|d| is_username(d))?.into())
+                    }
+                    "privatepost" => LinkChat::Id(parse_bare_channel_id(&query("channel")?)?),
+                    _ => return None,
+                };
+                Self::new(chat, MessageId(query_id("post")?.ok()?))
+            }
+            _ => return None,
+        };
+
+        link.single = query("single").is_some();
+        link.thread_id = query_id("thread")
+            .transpose()
+            .ok()?
+            .map(|id| ThreadId(MessageId(id)));
+        link.comment_id = query_id("comment").transpose().ok()?.map(MessageId);
+        Some(link)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TgLink {
+    Message(MessageLink),
+    // A user, bot, group or channel
+    Username(String),
+    User(UserId),
+    Phone(String),
+    Invite(String),
+    // `?start=` for private chats, `?startgroup=` for groups
+    StartParam {
+        bot: String,
+        param: String,
+        group: bool,
+    },
+    StickerSet(String),
+    EmojiSet(String),
+}
+
+pub fn parse_tg_link(url: &Url) -> Option<TgLink> {
+    if let Some(link) = MessageLink::parse(url) {
+        return Some(TgLink::Message(link));
+    }
+
+    let query = |key: &str| {
+        url.query_pairs()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value.into_owned())
+    };
+    let resolve = |username: &str| {
+        let start = |param: Option<String>, group| {
+            param.map(|param| TgLink::StartParam {
+                bot: username.into(),
+                param,
+                group,
+            })
+        };
+        start(query("start"), false)
+            .or_else(|| start(query("startgroup"), true))
+            .unwrap_or_else(|| TgLink::Username(username.into()))
+    };
+
+    let link = match url.scheme() {
+        "https" | "http" if is_tme_host(url.host_str()?) => {
+            let segments: Vec<_> = url.path_segments()?.filter(|s| !s.is_empty()).collect();
+            match segments.as_slice() {
+                ["joinchat", hash] => TgLink::Invite((*hash).into()),
+                ["addstickers", name] => TgLink::StickerSet((*name).into()),
+                ["addemoji", name] => TgLink::EmojiSet((*name).into()),
+                [plus] if plus.starts_with('+') => {
+                    let rest = &plus[1..];
+                    if rest.is_empty() {
+                        return None;
+                    } else if rest.chars().all(|ch| ch.is_ascii_digit()) {
+                        TgLink::Phone(rest.into())
+                    } else {
+                        TgLink::Invite(rest.into())
+                    }
+                }
+                [username] if is_username(username) => resolve(username),
+                _ => return None,
+            }
+        }
+        "tg" => match url.host_str()? {
+            "resolve" => match (query("domain"), query("phone")) {
+                (Some(domain), _) if is_username(&domain) => resolve(&domain),
+                (None, Some(phone)) => TgLink::Phone(phone),
+                _ => return None,
+            },
+            "user" => TgLink::User(UserId(query("id")?.parse().ok()?)),
+            "openmessage" => TgLink::User(UserId(query("user_id")?.parse().ok()?)),
+            "join" => TgLink::Invite(query("invite")?),
+            "addstickers" => TgLink::StickerSet(query("set")?),
+            "addemoji" => TgLink::EmojiSet(query("set")?),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some(link)
+}
+
+fn is_tme_host(host: &str) -> bool {
+    matches!(host, "t.me" | "telegram.me" | "telegram.dog")
+}
+
+// Paths of `t.me` links which aren't usernames
+const RESERVED_PATHS: &[&str] = &[
+    "addemoji",
+    "addlist",
+    "addstickers",
+    "addtheme",
+    "bg",
+    "boost",
+    "c",
+    "invoice",
+    "iv",
+    "joinchat",
+    "login",
+    "proxy",
+    "s",
+    "setlanguage",
+    "share",
+    "socks",
+];
+
+fn is_username(username: &str) -> bool {
+    !RESERVED_PATHS.contains(&username)
+        && username.starts_with(|ch: char| ch.is_ascii_alphabetic())
+        && username
+            .chars()
+            .all(|ch| ch.is_ascii_alphanumeric() || ch == '_')
+}
+
+fn parse_bare_channel_id(id: &str) -> Option<ChatId> {
+    let id: i64 = id.parse().ok()?;
+    (1..=MAX_BARE_CHANNEL_ID)
+        .contains(&id)
+        .then(|| ChatId(MAX_MARKED_CHANNEL_ID - id))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{self as json, json};
+
+    use super::*;
+
+    fn url(url: &str) -> Url {
+        url.try_into().unwrap()
+    }
+
+    fn private_link() -> MessageLink {
+        MessageLink::new(LinkChat::Id(ChatId(-1001234567890)), MessageId(42))
+    }
+
+    #[test]
+    fn to_url() {
+        assert_eq!(
+            private_link().to_url(),
+            Some(url("https://t.me/c/1234567890/42"))
+        );
+        assert_eq!(
+            private_link().topic(ThreadId(MessageId(7))).to_url(),
+            Some(url("https://t.me/c/1234567890/7/42"))
+        );
+        assert_eq!(
+            MessageLink::new(LinkChat::Username("catnews".into()), MessageId(42))
+                .comment(MessageId(3))
+                .to_url(),
+            Some(url("https://t.me/catnews/42?comment=3"))
+        );
+        assert_eq!(
+            MessageLink::new(LinkChat::Username("catchat".into()), MessageId(42))
+                .thread(ThreadId(MessageId(5)))
+                .single()
+                .to_url(),
+            Some(url("https://t.me/catchat/42?single&thread=5"))
+        );
+
+        // Private chats and basic groups have no message links
+        assert_eq!(
+            MessageLink::new(LinkChat::Id(ChatId(42)), MessageId(1)).to_url(),
+            None
+        );
+        assert_eq!(
+            MessageLink::new(LinkChat::Id(ChatId(-42)), MessageId(1)).to_url(),
+            None
+        );
+    }
+
+    #[test]
+    fn of_message() {
+        let msg: Message = json::from_value(json!({
+            "message_id": 42,
+            "date": 0,
+            "chat": { "id": -1001234567890i64, "type": "supergroup", "title": "Cats", "is_forum": true },
+            "is_topic_message": true,
+            "message_thread_id": 7,
+            "text": "meow",
+        }))
+        .unwrap();
+        assert_eq!(
+            MessageLink::of_message(&msg),
+            Some(private_link().topic(ThreadId(MessageId(7))))
+        );
+
+        let msg: Message = json::from_value(json!({
+            "message_id": 42,
+            "date": 0,
+            "chat": { "id": 1, "type": "private", "first_name": "Cat" },
+            "text": "meow",
+        }))
+        .unwrap();
+        assert_eq!(MessageLink::of_message(&msg), None);
+    }
+
+    #[test]
+    fn parse() {
+        let links = [
+            private_link(),
+            private_link().topic(ThreadId(MessageId(7))),
+            private_link().thread(ThreadId(MessageId(5))).single(),
+            MessageLink::new(LinkChat::Username("catnews".into()), MessageId(42))
+                .comment(MessageId(3)),
+            MessageLink::new(LinkChat::Username("catchat".into()), MessageId(42))
+                .topic(ThreadId(MessageId(7)))
+                .thread(ThreadId(MessageId(5))),
+        ];
+        for link in links {
+            assert_eq!(MessageLink::parse(&link.to_url().unwrap()), Some(link));
+        }
+
+        assert_eq!(
+            MessageLink::parse(&url("http://telegram.me/c/1234567890/42/?t=10")),
+            Some(private_link())
+        );
+        assert_eq!(
+            MessageLink::parse(&url("tg://privatepost?channel=1234567890&post=42&single")),
+            Some(private_link().single())
+        );
+        assert_eq!(
+            MessageLink::parse(&url("tg://resolve?domain=catnews&post=42&comment=3")),
+            Some(
+                MessageLink::new(LinkChat::Username("catnews".into()), MessageId(42))
+                    .comment(MessageId(3))
+            )
+        );
+
+        for invalid in [
+            "https://t.me/catnews",
+            "https://t.me/c/1234567890",
+            "https://t.me/c/-1/42",
+            "https://t.me/c/99999999999999/42",
+            "https://t.me/catnews/meow",
+            "https://t.me/catnews/1/2/3",
+            "https://t.me/+invite/42",
+            "https://t.me/addstickers/42",
+            "https://t.me/catnews/42?comment=meow",
+            "https://example.com/catnews/42",
+            "tg://resolve?domain=catnews",
+            "tg://user?id=42",
+        ] {
+            assert_eq!(MessageLink::parse(&url(invalid)), None, "{invalid}");
+        }
+    }
+
+    #[test]
+    fn parse_tg_link() {
+        let parse = |link: &str| super::parse_tg_link(&url(link));
+
+        assert_eq!(
+            parse("https://t.me/c/1234567890/42"),
+            Some(TgLink::Message(private_link()))
+        );
+        assert_eq!(
+            parse("https://t.me/catnews"),
+            Some(TgLink::Username("catnews".into()))
+        );
+        assert_eq!(
+            parse("tg://resolve?domain=catnews"),
+            Some(TgLink::Username("catnews".into()))
+        );
+        assert_eq!(parse("tg://user?id=42"), Some(TgLink::User(UserId(42))));
+        assert_eq!(parse("tg://user/?id=42"), Some(TgLink::User(UserId(42))));
+        assert_eq!(
+            parse("tg://openmessage?user_id=42"),
+            Some(TgLink::User(UserId(42)))
+        );
+        assert_eq!(
+            parse("https://t.me/+15551234567"),
+            Some(TgLink::Phone("15551234567".into()))
+        );
+        assert_eq!(
+            parse("tg://resolve?phone=15551234567"),
+            Some(TgLink::Phone("15551234567".into()))
+        );
+        for invite in [
+            "https://t.me/+AbCd_123",
+            "https://t.me/joinchat/AbCd_123",
+            "tg://join?invite=AbCd_123",
+        ] {
+            assert_eq!(parse(invite), Some(TgLink::Invite("AbCd_123".into())));
+        }
+        assert_eq!(
+            parse("https://t.me/catbot?start=meow"),
+            Some(TgLink::StartParam {
+                bot: "catbot".into(),
+                param: "meow".into(),
+                group: false,
+            })
+        );
+        assert_eq!(
+            parse("tg://resolve?domain=catbot&startgroup=meow"),
+            Some(TgLink::StartParam {
+                bot: "catbot".into(),
+                param: "meow".into(),
+                group: true,
+            })
+        );
+        assert_eq!(
+            parse("https://t.me/addstickers/Cats"),
+            Some(TgLink::StickerSet("Cats".into()))
+        );
+        assert_eq!(
+            parse("tg://addemoji?set=Cats"),
+            Some(TgLink::EmojiSet("Cats".into()))
+        );
+
+        for invalid in [
+            "https://t.me/",
+            "https://t.me/+",
+            "https://t.me/joinchat",
+            "https://t.me/share/url",
+            "https://example.com/catnews",
+            "tg://user?id=cat",
+            "tg://settings",
+        ] {
+            assert_eq!(parse(invalid), None, "{invalid}");
+        }
+    }
+}
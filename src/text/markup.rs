@@ -0,0 +1,1173 @@
+use std::cmp::Reverse;
+
+use teloxide::types::{MessageEntity, MessageEntityKind};
+
+use super::{entity_end, MessageText};
+
+impl<'a> MessageText<'a> {
+    pub fn to_html(&self) -> String {
+        render::<Html>(self)
+    }
+
+    pub fn to_markdown_v2(&self) -> String {
+        render::<MarkdownV2>(self)
+    }
+}
+
+trait Markup {
+    fn escape(ch: char, in_code: bool, output: &mut String);
+
+    fn open(kind: &MessageEntityKind, output: &mut String);
+
+    fn close(kind: &MessageEntityKind, output: &mut String);
+}
+
+// Entities like `Url` or `Hashtag` are detected by Telegram itself, so they
+// have no markup representation.
+fn has_markup(kind: &MessageEntityKind) -> bool {
+    use MessageEntityKind::*;
+
+    match kind {
+        Bold
+        | Italic
+        | Underline
+        | Strikethrough
+        | Spoiler
+        | Code
+        | Pre { .. }
+        | TextLink { .. }
+        | TextMention { .. }
+        | CustomEmoji { .. } => true,
+        Blockquote | Mention | Hashtag | Cashtag | BotCommand | Url | Email | PhoneNumber => false,
+    }
+}
+
+fn is_code(kind: &MessageEntityKind) -> bool {
+    matches!(
+        kind,
+        MessageEntityKind::Code | MessageEntityKind::Pre { .. }
+    )
+}
+
+// Markup requires tags to be properly nested, while entities are allowed to
+// overlap. When an entity ends inside another one that was opened later, the
+// inner ones are closed first and reopened right after.
+fn render<M: Markup>(text: &MessageText) -> String {
+    let mut entities: Vec<&MessageEntity> = text
+        .entities()
+        .iter()
+        .filter(|entity| entity.length != 0 && has_markup(&entity.kind))
+        .collect();
+    entities.sort_by_key(|entity| (entity.offset, Reverse(entity.length)));
+
+    let mut pending = entities.into_iter().peekable();
+    let mut opened: Vec<&MessageEntity> = vec![];
+    let mut output = String::with_capacity(text.text().len());
+    let mut offset = 0;
+
+    for ch in text.text().chars() {
+        if let Some(first_ended) = opened
+            .iter()
+            .position(|entity| entity_end(entity) <= offset)
+        {
+            let closed = opened.split_off(first_ended);
+            closed
+                .iter()
+                .rev()
+                .for_each(|entity| M::close(&entity.kind, &mut output));
+            for entity in closed {
+                if entity_end(entity) > offset {
+                    M::open(&entity.kind, &mut output);
+                    opened.push(entity);
+                }
+            }
+        }
+
+        while let Some(entity) = pending.next_if(|entity| entity.offset <= offset) {
+            M::open(&entity.kind, &mut output);
+            opened.push(entity);
+        }
+
+        let in_code = opened.iter().any(|entity| is_code(&entity.kind));
+        M::escape(ch, in_code, &mut output);
+        offset += ch.len_utf16();
+    }
+
+    opened
+        .iter()
+        .rev()
+        .for_each(|entity| M::close(&entity.kind, &mut output));
+
+    output
+}
+
+struct Html;
+
+impl Html {
+    fn escape_attr(value: &str, output: &mut String) {
+        for ch in value.chars() {
+            match ch {
+                '"' => output.push_str("&quot;"),
+                _ => Self::escape(ch, false, output),
+            }
+        }
+    }
+}
+
+impl Markup for Html {
+    fn escape(ch: char, _in_code: bool, output: &mut String) {
+        match ch {
+            '&' => output.push_str("&amp;"),
+            '<' => output.push_str("&lt;"),
+            '>' => output.push_str("&gt;"),
+            _ => output.push(ch),
+        }
+    }
+
+    fn open(kind: &MessageEntityKind, output: &mut String) {
+        use MessageEntityKind::*;
+
+        match kind {
+            Bold => output.push_str("<b>"),
+            Italic => output.push_str("<i>"),
+            Underline => output.push_str("<u>"),
+            Strikethrough => output.push_str("<s>"),
+            Spoiler => output.push_str("<tg-spoiler>"),
+            Code => output.push_str("<code>"),
+            Pre { language: None } => output.push_str("<pre>"),
+            Pre {
+                language: Some(language),
+            } => {
+                output.push_str("<pre><code class=\"language-");
+                Self::escape_attr(language, output);
+                output.push_str("\">");
+            }
+            TextLink { url } => {
+                output.push_str("<a href=\"");
+                Self::escape_attr(url.as_str(), output);
+                output.push_str("\">");
+            }
+            TextMention { user } => {
+                output.push_str("<a href=\"");
+                Self::escape_attr(user.url().as_str(), output);
+                output.push_str("\">");
+            }
+            CustomEmoji { custom_emoji_id } => {
+                output.push_str("<tg-emoji emoji-id=\"");
+                Self::escape_attr(custom_emoji_id, output);
+                output.push_str("\">");
+            }
+            Blockquote | Mention | Hashtag | Cashtag | BotCommand | Url | Email | PhoneNumber => {}
+        }
+    }
+
+    fn close(kind: &MessageEntityKind, output: &mut String) {
+        use MessageEntityKind::*;
+
+        match kind {
+            Bold => output.push_str("</b>"),
+            Italic => output.push_str("</i>"),
+            Underline => output.push_str("</u>"),
+            Strikethrough => output.push_str("</s>"),
+            Spoiler => output.push_str("</tg-spoiler>"),
+            Code => output.push_str("</code>"),
+            Pre { language: None } => output.push_str("</pre>"),
+            Pre { language: Some(_) } => output.push_str("</code></pre>"),
+            TextLink { .. } | TextMention { .. } => output.push_str("</a>"),
+            CustomEmoji { .. } => output.push_str("</tg-emoji>"),
+            Blockquote | Mention | Hashtag | Cashtag | BotCommand | Url | Email | PhoneNumber => {}
+        }
+    }
+}
+
+struct MarkdownV2;
+
+impl MarkdownV2 {
+    // `__` is always greedily treated as underline, so an italic marker directly
+    // followed by an underline one must be separated by `\r`, which Telegram
+    // ignores. An escaped `\_` is text and needs no separator.
+    fn push_marker(marker: &str, output: &mut String) {
+        if let Some(before) = output.strip_suffix('_') {
+            let backslashes = before.chars().rev().take_while(|&ch| ch == '\\').count();
+            if backslashes % 2 == 0 && marker.starts_with('_') {
+                output.push('\r');
+            }
+        }
+        output.push_str(marker);
+    }
+
+    fn close_link(url: &str, output: &mut String) {
+        output.push_str("](");
+        for ch in url.chars() {
+            if ch == ')' || ch == '\\' {
+                output.push('\\');
+            }
+            output.push(ch);
+        }
+        output.push(')');
+    }
+}
+
+impl Markup for MarkdownV2 {
+    fn escape(ch: char, in_code: bool, output: &mut String) {
+        let special = if in_code {
+            matches!(ch, '`' | '\\')
+        } else {
+            "_*[]()~`>#+-=|{}.!\\".contains(ch)
+        };
+        if special {
+            output.push('\\');
+        }
+        output.push(ch);
+    }
+
+    fn open(kind: &MessageEntityKind, output: &mut String) {
+        use MessageEntityKind::*;
+
+        match kind {
+            Bold => Self::push_marker("*", output),
+            Italic => Self::push_marker("_", output),
+            Underline => Self::push_marker("__", output),
+            Strikethrough => Self::push_marker("~", output),
+            Spoiler => Self::push_marker("||", output),
+            Code => Self::push_marker("`", output),
+            Pre { language } => {
+                Self::push_marker("```", output);
+                if let Some(language) = language {
+                    output.push_str(language);
+                }
+                output.push('\n');
+            }
+            TextLink { .. } | TextMention { .. } => Self::push_marker("[", output),
+            CustomEmoji { .. } => Self::push_marker("![", output),
+            Blockquote | Mention | Hashtag | Cashtag | BotCommand | Url | Email | PhoneNumber => {}
+        }
+    }
+
+    fn close(kind: &MessageEntityKind, output: &mut String) {
+        use MessageEntityKind::*;
+
+        match kind {
+            Bold => Self::push_marker("*", output),
+            Italic => Self::push_marker("_", output),
+            Underline => Self::push_marker("__", output),
+            Strikethrough => Self::push_marker("~", output),
+            Spoiler => Self::push_marker("||", output),
+            Code => Self::push_marker("`", output),
+            Pre { .. } => Self::push_marker("```", output),
+            TextLink { url } => Self::close_link(url.as_str(), output),
+            TextMention { user } => Self::close_link(user.url().as_str(), output),
+            CustomEmoji { custom_emoji_id } => {
+                Self::close_link(&format!("tg://emoji?id={custom_emoji_id}"), output)
+            }
+            Blockquote | Mention | Hashtag | Cashtag | BotCommand | Url | Email | PhoneNumber => {}
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum MarkupError {
+    #[error("`{tag}` is never closed")]
+    Unclosed { tag: String },
+
+    #[error("unexpected closing `{tag}` at byte {position}")]
+    UnexpectedClose { tag: String, position: usize },
+
+    #[error("unterminated tag at byte {position}")]
+    UnterminatedTag { position: usize },
+
+    #[error("unsupported tag `{tag}` at byte {position}")]
+    UnsupportedTag { tag: String, position: usize },
+
+    #[error("tag `{tag}` at byte {position} is missing attribute `{attribute}`")]
+    MissingAttribute {
+        tag: String,
+        attribute: &'static str,
+        position: usize,
+    },
+
+    #[error("link at byte {position} is missing its url")]
+    MissingUrl { position: usize },
+
+    #[error("invalid url `{url}` at byte {position}")]
+    InvalidUrl { url: String, position: usize },
+
+    #[error("bad escape sequence at byte {position}")]
+    BadEscape { position: usize },
+
+    #[error("reserved character `{ch}` at byte {position} must be escaped")]
+    Reserved { ch: char, position: usize },
+}
+
+type Result<T> = std::result::Result<T, MarkupError>;
+
+impl<'a> MessageText<'a> {
+    pub fn from_html(html: &str) -> Result<Self> {
+        HtmlParser::new(html).parse()
+    }
+
+    pub fn from_markdown_v2(markdown: &str) -> Result<Self> {
+        MarkdownV2Parser::new(markdown).parse()
+    }
+}
+
+#[derive(Default)]
+struct Output {
+    text: String,
+    offset: usize,
+    entities: Vec<MessageEntity>,
+}
+
+impl Output {
+    fn push(&mut self, ch: char) {
+        self.text.push(ch);
+        self.offset += ch.len_utf16();
+    }
+
+    fn push_str(&mut self, text: &str) {
+        self.text.push_str(text);
+        self.offset += text.encode_utf16().count();
+    }
+
+    fn add_entity(&mut self, kind: MessageEntityKind, offset: usize) {
+        if self.offset > offset {
+            self.entities.push(MessageEntity {
+                kind,
+                offset,
+                length: self.offset - offset,
+            });
+        }
+    }
+
+    fn finish<'a>(mut self) -> MessageText<'a> {
+        // Entities are added when they are closed, i.e. inner ones first
+        self.entities.reverse();
+        self.entities
+            .sort_by_key(|entity| (entity.offset, Reverse(entity.length)));
+        MessageText::new(self.text, self.entities)
+    }
+}
+
+fn parse_url(url: &str, position: usize) -> Result<url::Url> {
+    url::Url::parse(url).map_err(|_| MarkupError::InvalidUrl {
+        url: url.into(),
+        position,
+    })
+}
+
+struct HtmlOpenTag {
+    name: String,
+    kind: Option<MessageEntityKind>,
+    offset: usize,
+}
+
+struct HtmlParser<'a> {
+    input: &'a str,
+    position: usize,
+    output: Output,
+    opened: Vec<HtmlOpenTag>,
+}
+
+impl<'a> HtmlParser<'a> {
+    fn new(input: &'a str) -> Self {
+        Self {
+            input,
+            position: 0,
+            output: Output::default(),
+            opened: vec![],
+        }
+    }
+
+    fn parse<'b>(mut self) -> Result<MessageText<'b>> {
+        while let Some(ch) = self.input[self.position..].chars().next() {
+            match ch {
+                '<' => self.parse_tag()?,
+                '&' => {
+                    let ch = self.parse_char_ref()?;
+                    self.output.push(ch);
+                }
+                _ => {
+                    self.output.push(ch);
+                    self.position += ch.len_utf8();
+                }
+            }
+        }
+
+        match self.opened.pop() {
+            Some(tag) => Err(MarkupError::Unclosed { tag: tag.name }),
+            None => Ok(self.output.finish()),
+        }
+    }
+
+    fn parse_char_ref(&mut self) -> Result<char> {
+        let position = self.position;
+        let bad_escape = || MarkupError::BadEscape { position };
+
+        let end = self.input[position..].find(';').ok_or_else(bad_escape)? + position;
+        let name = &self.input[position + 1..end];
+        let ch = match name {
+            "lt" => '<',
+            "gt" => '>',
+            "amp" => '&',
+            "quot" => '"',
+            _ => {
+                let code = if let Some(hex) = name.strip_prefix("#x").or(name.strip_prefix("#X")) {
+                    u32::from_str_radix(hex, 16).ok()
+                } else if let Some(dec) = name.strip_prefix('#') {
+                    dec.parse().ok()
+                } else {
+                    None
+                };
+                code.and_then(char::from_u32).ok_or_else(bad_escape)?
+            }
+        };
+
+        self.position = end + 1;
+        Ok(ch)
+    }
+
+    fn parse_tag(&mut self) -> Result<()> {
+        let position = self.position;
+        let end = self.input[position..]
+            .find('>')
+            .ok_or(MarkupError::UnterminatedTag { position })?
+            + position;
+        let tag = &self.input[position + 1..end];
+        self.position = end + 1;
+
+        if let Some(name) = tag.strip_prefix('/') {
+            let name = name.trim().to_ascii_lowercase();
+            return match self.opened.pop() {
+                Some(open) if open.name == name => {
+                    if let Some(kind) = open.kind {
+                        self.output.add_entity(kind, open.offset);
+                    }
+                    Ok(())
+                }
+                _ => Err(MarkupError::UnexpectedClose {
+                    tag: name,
+                    position,
+                }),
+            };
+        }
+
+        let (name, attrs) = match tag.split_once(char::is_whitespace) {
+            Some((name, attrs)) => (name.to_ascii_lowercase(), attrs),
+            None => (tag.to_ascii_lowercase(), ""),
+        };
+        let attrs = parse_html_attrs(attrs, position)?;
+        let attr = |attribute: &'static str| {
+            attrs
+                .iter()
+                .find(|(name, _)| name == attribute)
+                .map(|(_, value)| value.as_str())
+                .ok_or_else(|| MarkupError::MissingAttribute {
+                    tag: name.clone(),
+                    attribute,
+                    position,
+                })
+        };
+
+        use MessageEntityKind::*;
+
+        let kind = match name.as_str() {
+            "b" | "strong" => Some(Bold),
+            "i" | "em" => Some(Italic),
+            "u" | "ins" => Some(Underline),
+            "s" | "strike" | "del" => Some(Strikethrough),
+            "tg-spoiler" => Some(Spoiler),
+            "span" if attr("class") == Ok("tg-spoiler") => Some(Spoiler),
+            "pre" => Some(Pre { language: None }),
+            "code" => match self.opened.last_mut() {
+                // `<pre><code class="language-xxx">` is a single pre entity
+                Some(HtmlOpenTag {
+                    kind: Some(Pre { language }),
+                    offset,
+                    ..
+                }) if *offset == self.output.offset => {
+                    *language = attr("class")
+                        .ok()
+                        .and_then(|class| class.strip_prefix("language-"))
+                        .map(Into::into);
+                    None
+                }
+                _ => Some(Code),
+            },
+            // A text mention only keeps the id of its user in the link, so it
+            // reads back as a text link to `tg://user/?id=...`
+            "a" => Some(TextLink {
+                url: parse_url(attr("href")?, position)?,
+            }),
+            "tg-emoji" => Some(CustomEmoji {
+                custom_emoji_id: attr("emoji-id")?.into(),
+            }),
+            _ => {
+                return Err(MarkupError::UnsupportedTag {
+                    tag: name,
+                    position,
+                })
+            }
+        };
+
+        self.opened.push(HtmlOpenTag {
+            name,
+            kind,
+            offset: self.output.offset,
+        });
+        Ok(())
+    }
+}
+
+fn parse_html_attrs(input: &str, position: usize) -> Result<Vec<(String, String)>> {
+    let mut attrs = vec![];
+    let mut input = input.trim_start();
+
+    while !input.is_empty() {
+        let name_end = input
+            .find(|ch: char| ch == '=' || ch.is_whitespace())
+            .unwrap_or(input.len());
+        let name = input[..name_end].to_ascii_lowercase();
+        input = input[name_end..].trim_start();
+
+        let value = match input.strip_prefix('=') {
+            Some(rest) => {
+                let rest = rest.trim_start();
+                let (value, rest) = match rest.chars().next() {
+                    Some(quote @ ('"' | '\'')) => rest[1..]
+                        .split_once(quote)
+                        .ok_or(MarkupError::UnterminatedTag { position })?,
+                    _ => rest.split_at(rest.find(char::is_whitespace).unwrap_or(rest.len())),
+                };
+                input = rest.trim_start();
+                decode_html_attr(value, position)?
+            }
+            None => String::new(),
+        };
+        attrs.push((name, value));
+    }
+
+    Ok(attrs)
+}
+
+fn decode_html_attr(value: &str, position: usize) -> Result<String> {
+    if !value.contains('&') {
+        return Ok(value.into());
+    }
+
+    let mut parser = HtmlParser::new(value);
+    let mut decoded = String::with_capacity(value.len());
+    while let Some(ch) = value[parser.position..].chars().next() {
+        if ch == '&' {
+            decoded.push(
+                parser
+                    .parse_char_ref()
+                    .map_err(|_| MarkupError::BadEscape { position })?,
+            );
+        } else {
+            decoded.push(ch);
+            parser.position += ch.len_utf8();
+        }
+    }
+    Ok(decoded)
+}
+
+struct MarkdownV2OpenMarker {
+    marker: &'static str,
+    offset: usize,
+    position: usize,
+}
+
+struct MarkdownV2Parser<'a> {
+    input: &'a str,
+    position: usize,
+    output: Output,
+    opened: Vec<MarkdownV2OpenMarker>,
+}
+
+impl<'a> MarkdownV2Parser<'a> {
+    fn new(input: &'a str) -> Self {
+        Self {
+            input,
+            position: 0,
+            output: Output::default(),
+            opened: vec![],
+        }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.input[self.position..]
+    }
+
+    fn parse<'b>(mut self) -> Result<MessageText<'b>> {
+        use MessageEntityKind::*;
+
+        while let Some(ch) = self.rest().chars().next() {
+            let position = self.position;
+            let rest = self.rest();
+
+            match ch {
+                '\\' => {
+                    let ch = self.parse_escape()?;
+                    self.output.push(ch);
+                }
+                '\r' if self.input[..position].ends_with('_') && rest[1..].starts_with('_') => {
+                    self.position += 1;
+                }
+                '`' => self.parse_code()?,
+                '*' => self.toggle("*", Bold)?,
+                '~' => self.toggle("~", Strikethrough)?,
+                '_' if rest.starts_with("__") => self.toggle("__", Underline)?,
+                '_' => self.toggle("_", Italic)?,
+                '|' if rest.starts_with("||") => self.toggle("||", Spoiler)?,
+                '[' => self.open("["),
+                '!' if rest.starts_with("![") => self.open("!["),
+                ']' => self.parse_link()?,
+                _ if "[]()~`>#+-=|{}.!".contains(ch) => {
+                    return Err(MarkupError::Reserved { ch, position })
+                }
+                _ => {
+                    self.output.push(ch);
+                    self.position += ch.len_utf8();
+                }
+            }
+        }
+
+        match self.opened.pop() {
+            Some(open) => Err(MarkupError::Unclosed {
+                tag: open.marker.into(),
+            }),
+            None => Ok(self.output.finish()),
+        }
+    }
+
+    fn parse_escape(&mut self) -> Result<char> {
+        let position = self.position;
+        match self.input[position + 1..].chars().next() {
+            Some(ch) if ('\u{1}'..='\u{7e}').contains(&ch) => {
+                self.position += 2;
+                Ok(ch)
+            }
+            _ => Err(MarkupError::BadEscape { position }),
+        }
+    }
+
+    fn open(&mut self, marker: &'static str) {
+        self.opened.push(MarkdownV2OpenMarker {
+            marker,
+            offset: self.output.offset,
+            position: self.position,
+        });
+        self.position += marker.len();
+    }
+
+    fn toggle(&mut self, marker: &'static str, kind: MessageEntityKind) -> Result<()> {
+        match self.opened.iter().rposition(|open| open.marker == marker) {
+            None => {
+                self.open(marker);
+                Ok(())
+            }
+            Some(index) if index + 1 == self.opened.len() => {
+                let open = self.opened.pop().unwrap();
+                self.output.add_entity(kind, open.offset);
+                self.position += marker.len();
+                Ok(())
+            }
+            Some(_) => Err(MarkupError::UnexpectedClose {
+                tag: marker.into(),
+                position: self.position,
+            }),
+        }
+    }
+
+    fn parse_code(&mut self) -> Result<()> {
+        let marker = if self.rest().starts_with("```") {
+            "```"
+        } else {
+            "`"
+        };
+        self.position += marker.len();
+
+        let mut code = String::new();
+        loop {
+            let rest = self.rest();
+            if rest.starts_with(marker) {
+                self.position += marker.len();
+                break;
+            }
+            match rest.chars().next() {
+                Some('\\') => code.push(self.parse_escape()?),
+                Some(ch) => {
+                    code.push(ch);
+                    self.position += ch.len_utf8();
+                }
+                None => return Err(MarkupError::Unclosed { tag: marker.into() }),
+            }
+        }
+
+        let offset = self.output.offset;
+        let kind = if marker == "`" {
+            self.output.push_str(&code);
+            MessageEntityKind::Code
+        } else {
+            let (language, code) = match code.split_once('\n') {
+                Some((language, code)) => (Some(language.trim()).filter(|l| !l.is_empty()), code),
+                None => (None, code.as_str()),
+            };
+            self.output.push_str(code);
+            MessageEntityKind::Pre {
+                language: language.map(Into::into),
+            }
+        };
+        self.output.add_entity(kind, offset);
+        Ok(())
+    }
+
+    fn parse_link(&mut self) -> Result<()> {
+        let position = self.position;
+        let open = match self.opened.last() {
+            Some(open) if open.marker == "[" || open.marker == "![" => self.opened.pop().unwrap(),
+            _ => {
+                return Err(MarkupError::UnexpectedClose {
+                    tag: "]".into(),
+                    position,
+                })
+            }
+        };
+
+        self.position += 1;
+        if !self.rest().starts_with('(') {
+            return Err(MarkupError::MissingUrl {
+                position: open.position,
+            });
+        }
+        self.position += 1;
+
+        let mut url = String::new();
+        loop {
+            match self.rest().chars().next() {
+                Some(')') => {
+                    self.position += 1;
+                    break;
+                }
+                Some('\\') => url.push(self.parse_escape()?),
+                Some(ch) => {
+                    url.push(ch);
+                    self.position += ch.len_utf8();
+                }
+                None => return Err(MarkupError::Unclosed { tag: "(".into() }),
+            }
+        }
+
+        let url = parse_url(&url, open.position)?;
+        let kind = if open.marker == "[" {
+            MessageEntityKind::TextLink { url }
+        } else {
+            let custom_emoji_id = (url.scheme() == "tg" && url.host_str() == Some("emoji"))
+                .then(|| url.query_pairs().find(|(key, _)| key == "id"))
+                .flatten()
+                .ok_or_else(|| MarkupError::InvalidUrl {
+                    url: url.to_string(),
+                    position: open.position,
+                })?
+                .1
+                .into_owned();
+            MessageEntityKind::CustomEmoji { custom_emoji_id }
+        };
+        self.output.add_entity(kind, open.offset);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use teloxide::types::{User, UserId};
+
+    use super::*;
+    use crate::text::mtb;
+
+    fn entity(kind: MessageEntityKind, offset: usize, length: usize) -> MessageEntity {
+        MessageEntity {
+            kind,
+            offset,
+            length,
+        }
+    }
+
+    // The final state of the `appender_text_link` fixture in `text.rs`.
+    fn fixture() -> MessageText<'static> {
+        MessageText::new(
+            "I'm a Teapot!\nNya!\nlink\n\n喵呜🐱🥰\nMeow\nFat Cat",
+            vec![
+                entity(MessageEntityKind::Italic, 0, 6),
+                entity(
+                    MessageEntityKind::TextLink {
+                        url: "https://http.cat/418".try_into().unwrap(),
+                    },
+                    6,
+                    8,
+                ),
+                entity(
+                    MessageEntityKind::TextLink {
+                        url: "https://example.com".try_into().unwrap(),
+                    },
+                    19,
+                    4,
+                ),
+                entity(
+                    MessageEntityKind::TextLink {
+                        url: "https://http.cat".try_into().unwrap(),
+                    },
+                    25,
+                    6,
+                ),
+                entity(MessageEntityKind::Bold, 37, 7),
+            ],
+        )
+    }
+
+    #[test]
+    fn render_fixture() {
+        let msg_text = fixture();
+
+        assert_eq!(
+            msg_text.to_html(),
+            "<i>I'm a </i><a href=\"https://http.cat/418\">Teapot!\n</a>Nya!\n\
+             <a href=\"https://example.com/\">link</a>\n\n\
+             <a href=\"https://http.cat/\">喵呜🐱🥰</a>\nMeow\n<b>Fat Cat</b>"
+        );
+        assert_eq!(
+            msg_text.to_markdown_v2(),
+            "_I'm a _[Teapot\\!\n](https://http.cat/418)Nya\\!\n\
+             [link](https://example.com/)\n\n\
+             [喵呜🐱🥰](https://http.cat/)\nMeow\n*Fat Cat*"
+        );
+    }
+
+    #[test]
+    fn render_nested_and_overlapping() {
+        // Nested
+        let msg_text = MessageText::new(
+            "bold italic bold",
+            vec![
+                entity(MessageEntityKind::Bold, 0, 16),
+                entity(MessageEntityKind::Italic, 5, 6),
+            ],
+        );
+        assert_eq!(msg_text.to_html(), "<b>bold <i>italic</i> bold</b>");
+        assert_eq!(msg_text.to_markdown_v2(), "*bold _italic_ bold*");
+
+        // Overlapping
+        let msg_text = MessageText::new(
+            "aaa bbb ccc",
+            vec![
+                entity(MessageEntityKind::Bold, 0, 7),
+                entity(MessageEntityKind::Strikethrough, 4, 7),
+            ],
+        );
+        assert_eq!(msg_text.to_html(), "<b>aaa <s>bbb</s></b><s> ccc</s>");
+        assert_eq!(msg_text.to_markdown_v2(), "*aaa ~bbb~*~ ccc~");
+
+        // Italic directly followed by underline
+        let msg_text = MessageText::new(
+            "iu",
+            vec![
+                entity(MessageEntityKind::Underline, 0, 2),
+                entity(MessageEntityKind::Italic, 0, 2),
+            ],
+        );
+        assert_eq!(msg_text.to_html(), "<u><i>iu</i></u>");
+        assert_eq!(msg_text.to_markdown_v2(), "__\r_iu_\r__");
+
+        // Escaped underscores directly followed by a marker
+        let msg_text = mtb()
+            .plain("a_")
+            .italic("b")
+            .plain("\\_")
+            .underline("c")
+            .build();
+        assert_eq!(msg_text.to_markdown_v2(), "a\\__b_\\\\\\___c__");
+        assert_eq!(
+            MessageText::from_markdown_v2(&msg_text.to_markdown_v2()).unwrap(),
+            msg_text
+        );
+    }
+
+    #[test]
+    fn render_utf16_boundaries() {
+        let msg_text = mtb().plain("🐱").bold("喵🐟").plain("&<>").build();
+
+        assert_eq!(msg_text.to_html(), "🐱<b>喵🐟</b>&amp;&lt;&gt;");
+        assert_eq!(msg_text.to_markdown_v2(), "🐱*喵🐟*&<\\>");
+    }
+
+    #[test]
+    fn render_all_kinds() {
+        let user = User {
+            id: UserId(42),
+            is_bot: false,
+            first_name: "Cat".into(),
+            last_name: None,
+            username: None,
+            language_code: None,
+            is_premium: false,
+            added_to_attachment_menu: false,
+        };
+
+        let msg_text = MessageText::new(
+            "code_ pre` mention emoji /start tap.",
+            vec![
+                entity(MessageEntityKind::Code, 0, 5),
+                entity(
+                    MessageEntityKind::Pre {
+                        language: Some("rust".into()),
+                    },
+                    6,
+                    4,
+                ),
+                entity(MessageEntityKind::TextMention { user }, 11, 7),
+                entity(
+                    MessageEntityKind::CustomEmoji {
+                        custom_emoji_id: "123".into(),
+                    },
+                    19,
+                    5,
+                ),
+                entity(MessageEntityKind::BotCommand, 25, 6),
+                entity(MessageEntityKind::Spoiler, 32, 4),
+                entity(
+                    MessageEntityKind::TextLink {
+                        url: "https://example.com/(a)".try_into().unwrap(),
+                    },
+                    32,
+                    4,
+                ),
+            ],
+        );
+
+        assert_eq!(
+            msg_text.to_html(),
+            "<code>code_</code> <pre><code class=\"language-rust\">pre`</code></pre> \
+             <a href=\"tg://user/?id=42\">mention</a> \
+             <tg-emoji emoji-id=\"123\">emoji</tg-emoji> /start \
+             <tg-spoiler><a href=\"https://example.com/(a)\">tap.</a></tg-spoiler>"
+        );
+        assert_eq!(
+            msg_text.to_markdown_v2(),
+            "`code_` ```rust\npre\\```` [mention](tg://user/?id=42) \
+             ![emoji](tg://emoji?id=123) /start \
+             ||[tap\\.](https://example.com/(a\\))||"
+        );
+    }
+
+    #[test]
+    fn parse_round_trip() {
+        let fixtures = [
+            fixture(),
+            mtb()
+                .plain("🐱 ")
+                .bold("meow_*")
+                .plain(" [x](y) ")
+                .code("a`b\\c")
+                .pre("fn main() {}")
+                .spoiler("||secret||")
+                .build(),
+            MessageText::new(
+                "bold italic bold",
+                vec![
+                    entity(MessageEntityKind::Bold, 0, 16),
+                    entity(MessageEntityKind::Italic, 5, 6),
+                ],
+            ),
+            MessageText::new(
+                "iu",
+                vec![
+                    entity(MessageEntityKind::Underline, 0, 2),
+                    entity(MessageEntityKind::Italic, 0, 2),
+                ],
+            ),
+            MessageText::new(
+                "code emoji",
+                vec![
+                    entity(
+                        MessageEntityKind::Pre {
+                            language: Some("rust".into()),
+                        },
+                        0,
+                        4,
+                    ),
+                    entity(
+                        MessageEntityKind::CustomEmoji {
+                            custom_emoji_id: "123".into(),
+                        },
+                        5,
+                        5,
+                    ),
+                ],
+            ),
+        ];
+
+        for msg_text in fixtures {
+            assert_eq!(
+                MessageText::from_html(&msg_text.to_html()).unwrap(),
+                msg_text
+            );
+            assert_eq!(
+                MessageText::from_markdown_v2(&msg_text.to_markdown_v2()).unwrap(),
+                msg_text
+            );
+        }
+    }
+
+    #[test]
+    fn parse_text_mention() {
+        let user = User {
+            id: UserId(42),
+            is_bot: false,
+            first_name: "Cat".into(),
+            last_name: None,
+            username: None,
+            language_code: None,
+            is_premium: false,
+            added_to_attachment_menu: false,
+        };
+        let msg_text = MessageText::new(
+            "hi Cat",
+            vec![entity(MessageEntityKind::TextMention { user }, 3, 3)],
+        );
+        let expected = MessageText::new(
+            "hi Cat",
+            vec![entity(
+                MessageEntityKind::TextLink {
+                    url: "tg://user/?id=42".parse().unwrap(),
+                },
+                3,
+                3,
+            )],
+        );
+
+        // Only the id of the user survives, as a link
+        assert_eq!(
+            MessageText::from_html(&msg_text.to_html()).unwrap(),
+            expected
+        );
+        assert_eq!(
+            MessageText::from_markdown_v2(&msg_text.to_markdown_v2()).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn parse_html() {
+        assert_eq!(
+            MessageText::from_html(
+                "<strong>喵</strong> <span class=\"tg-spoiler\">&#x1F431;</span> \
+                 <a href='https://example.com/?a=1&amp;b=2'>&lt;link&gt;</a>"
+            )
+            .unwrap(),
+            MessageText::new(
+                "喵 🐱 <link>",
+                vec![
+                    entity(MessageEntityKind::Bold, 0, 1),
+                    entity(MessageEntityKind::Spoiler, 2, 2),
+                    entity(
+                        MessageEntityKind::TextLink {
+                            url: "https://example.com/?a=1&b=2".try_into().unwrap()
+                        },
+                        5,
+                        6
+                    ),
+                ]
+            )
+        );
+
+        assert_eq!(
+            MessageText::from_html("<b>bold"),
+            Err(MarkupError::Unclosed { tag: "b".into() })
+        );
+        assert_eq!(
+            MessageText::from_html("<b><i>bold</b></i>"),
+            Err(MarkupError::UnexpectedClose {
+                tag: "b".into(),
+                position: 10
+            })
+        );
+        assert_eq!(
+            MessageText::from_html("<b>bold</b"),
+            Err(MarkupError::UnterminatedTag { position: 7 })
+        );
+        assert_eq!(
+            MessageText::from_html("<br>"),
+            Err(MarkupError::UnsupportedTag {
+                tag: "br".into(),
+                position: 0
+            })
+        );
+        assert_eq!(
+            MessageText::from_html("<a>link</a>"),
+            Err(MarkupError::MissingAttribute {
+                tag: "a".into(),
+                attribute: "href",
+                position: 0
+            })
+        );
+        assert_eq!(
+            MessageText::from_html("<a href=\"nope\">link</a>"),
+            Err(MarkupError::InvalidUrl {
+                url: "nope".into(),
+                position: 0
+            })
+        );
+        assert_eq!(
+            MessageText::from_html("a &nbsp; b"),
+            Err(MarkupError::BadEscape { position: 2 })
+        );
+    }
+
+    #[test]
+    fn parse_markdown_v2() {
+        assert_eq!(
+            MessageText::from_markdown_v2("*bold \\*_italic_* ||spoiler|| ```\ncode\\`\n```")
+                .unwrap(),
+            MessageText::new(
+                "bold *italic spoiler code`\n",
+                vec![
+                    entity(MessageEntityKind::Bold, 0, 12),
+                    entity(MessageEntityKind::Italic, 6, 6),
+                    entity(MessageEntityKind::Spoiler, 13, 7),
+                    entity(MessageEntityKind::Pre { language: None }, 21, 6),
+                ]
+            )
+        );
+
+        assert_eq!(
+            MessageText::from_markdown_v2("*bold"),
+            Err(MarkupError::Unclosed { tag: "*".into() })
+        );
+        assert_eq!(
+            MessageText::from_markdown_v2("*bold _italic* text_"),
+            Err(MarkupError::UnexpectedClose {
+                tag: "*".into(),
+                position: 13
+            })
+        );
+        assert_eq!(
+            MessageText::from_markdown_v2("`code"),
+            Err(MarkupError::Unclosed { tag: "`".into() })
+        );
+        assert_eq!(
+            MessageText::from_markdown_v2("[link]"),
+            Err(MarkupError::MissingUrl { position: 0 })
+        );
+        assert_eq!(
+            MessageText::from_markdown_v2("end."),
+            Err(MarkupError::Reserved {
+                ch: '.',
+                position: 3
+            })
+        );
+        assert_eq!(
+            MessageText::from_markdown_v2("bad \\🐱"),
+            Err(MarkupError::BadEscape { position: 4 })
+        );
+    }
+}
@@ -0,0 +1,219 @@
+use teloxide::types::{Chat, Message, MessageEntityKind, MessageOrigin, User};
+
+use super::{chat_display_name, MessageText};
+
+pub fn user_display_name(user: &User) -> String {
+    let full_name = user.full_name();
+    let full_name = full_name.trim();
+
+    if !full_name.is_empty() {
+        full_name.into()
+    } else if let Some(username) = user.mention() {
+        username
+    } else {
+        format!("User {}", user.id)
+    }
+}
+
+// The chat a message was sent on behalf of (channel posts and anonymous
+// admins) takes precedence over the user who sent it.
+pub fn sender_display_name(msg: &Message) -> String {
+    if let Some(sender_chat) = &msg.sender_chat {
+        signed_chat_display_name(sender_chat, msg.author_signature())
+    } else if let Some(user) = &msg.from {
+        user_display_name(user)
+    } else {
+        "Unknown Sender".into()
+    }
+}
+
+pub fn forward_origin_display(msg: &Message) -> Option<String> {
+    let display = match msg.forward_origin()? {
+        MessageOrigin::User { sender_user, .. } => user_display_name(sender_user),
+        MessageOrigin::HiddenUser {
+            sender_user_name, ..
+        } => sender_user_name.clone(),
+        MessageOrigin::Chat {
+            sender_chat,
+            author_signature,
+            ..
+        } => signed_chat_display_name(sender_chat, author_signature.as_deref()),
+        MessageOrigin::Channel {
+            chat,
+            author_signature,
+            ..
+        } => signed_chat_display_name(chat, author_signature.as_deref()),
+    };
+    Some(display)
+}
+
+// `@username` if the user has one, otherwise their name linked to them, which
+// notifies them like a username mention does.
+pub fn mention(user: &User) -> MessageText<'static> {
+    let mut msg_text = MessageText::from("");
+    match user.mention() {
+        Some(username) => msg_text.append_text_with_entity(username, MessageEntityKind::Mention),
+        None => msg_text.append_text_with_entity(
+            user_display_name(user),
+            MessageEntityKind::TextMention { user: user.clone() },
+        ),
+    }
+    msg_text
+}
+
+fn signed_chat_display_name(chat: &Chat, author_signature: Option<&str>) -> String {
+    let name = chat_display_name(chat);
+    match author_signature {
+        Some(signature) => format!("{name} ({signature})"),
+        None => name.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{self as json, json};
+    use teloxide::types::{MessageEntity, UserId};
+
+    use super::*;
+
+    fn user(first_name: &str, last_name: Option<&str>, username: Option<&str>) -> User {
+        User {
+            id: UserId(42),
+            is_bot: false,
+            first_name: first_name.into(),
+            last_name: last_name.map(Into::into),
+            username: username.map(Into::into),
+            language_code: None,
+            is_premium: false,
+            added_to_attachment_menu: false,
+        }
+    }
+
+    fn message(fields: json::Value) -> Message {
+        let mut msg = json!({
+            "message_id": 1,
+            "date": 0,
+            "chat": { "id": -100123, "type": "supergroup", "title": "Cats" },
+            "text": "meow",
+        });
+        msg.as_object_mut()
+            .unwrap()
+            .extend(fields.as_object().unwrap().clone());
+        json::from_value(msg).unwrap()
+    }
+
+    #[test]
+    fn user_display_name() {
+        assert_eq!(
+            super::user_display_name(&user("Fat", Some("Cat"), None)),
+            "Fat Cat"
+        );
+        assert_eq!(super::user_display_name(&user("喵", None, None)), "喵");
+        assert_eq!(
+            super::user_display_name(&user(" ", None, Some("cat"))),
+            "@cat"
+        );
+        assert_eq!(super::user_display_name(&user("", None, None)), "User 42");
+    }
+
+    #[test]
+    fn sender_display_name() {
+        let from = json!({ "id": 42, "is_bot": false, "first_name": "Fat", "last_name": "Cat" });
+
+        // Sent by a user
+        assert_eq!(
+            super::sender_display_name(&message(json!({ "from": from }))),
+            "Fat Cat"
+        );
+        // Anonymous admin, with and without a custom title
+        let anonymous = json!({
+            "from": { "id": 1087968824, "is_bot": true, "first_name": "Group", "username": "GroupAnonymousBot" },
+            "sender_chat": { "id": -100123, "type": "supergroup", "title": "Cats" },
+        });
+        assert_eq!(
+            super::sender_display_name(&message(anonymous.clone())),
+            "Cats"
+        );
+        let mut signed = anonymous;
+        signed["author_signature"] = "Boss".into();
+        assert_eq!(super::sender_display_name(&message(signed)), "Cats (Boss)");
+        // Public channel
+        assert_eq!(
+            super::sender_display_name(&message(json!({
+                "sender_chat": { "id": -100456, "type": "channel", "title": "Cat News", "username": "catnews" },
+            }))),
+            "Cat News"
+        );
+        assert_eq!(
+            super::sender_display_name(&message(json!({}))),
+            "Unknown Sender"
+        );
+    }
+
+    #[test]
+    fn forward_origin_display() {
+        assert_eq!(super::forward_origin_display(&message(json!({}))), None);
+
+        let origin = |origin| message(json!({ "forward_origin": origin }));
+        assert_eq!(
+            super::forward_origin_display(&origin(json!({
+                "type": "user",
+                "date": 0,
+                "sender_user": { "id": 42, "is_bot": false, "first_name": "Fat", "last_name": "Cat" },
+            }))),
+            Some("Fat Cat".into())
+        );
+        assert_eq!(
+            super::forward_origin_display(&origin(json!({
+                "type": "hidden_user",
+                "date": 0,
+                "sender_user_name": "Hidden Cat",
+            }))),
+            Some("Hidden Cat".into())
+        );
+        assert_eq!(
+            super::forward_origin_display(&origin(json!({
+                "type": "chat",
+                "date": 0,
+                "sender_chat": { "id": -100123, "type": "supergroup", "title": "Cats" },
+                "author_signature": "Boss",
+            }))),
+            Some("Cats (Boss)".into())
+        );
+        assert_eq!(
+            super::forward_origin_display(&origin(json!({
+                "type": "channel",
+                "date": 0,
+                "chat": { "id": -100456, "type": "channel", "title": "Cat News", "username": "catnews" },
+                "message_id": 7,
+            }))),
+            Some("Cat News".into())
+        );
+    }
+
+    #[test]
+    fn mention() {
+        let msg_text = super::mention(&user("Fat", Some("Cat"), Some("cat")));
+        assert_eq!(msg_text.text(), "@cat");
+        assert_eq!(
+            msg_text.entities(),
+            vec![MessageEntity {
+                kind: MessageEntityKind::Mention,
+                offset: 0,
+                length: 4,
+            }]
+        );
+
+        let cat = user("喵", None, None);
+        let msg_text = super::mention(&cat);
+        assert_eq!(msg_text.text(), "喵");
+        assert_eq!(
+            msg_text.entities(),
+            vec![MessageEntity {
+                kind: MessageEntityKind::TextMention { user: cat },
+                offset: 0,
+                length: 1,
+            }]
+        );
+    }
+}
@@ -0,0 +1,394 @@
+use teloxide::types::{MessageEntity, MessageEntityKind};
+
+use super::{entity_end, mention::user_display_name, MessageText};
+
+// What a text mention, a name linked to a user without a username, is
+// rendered as
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextMentionStyle {
+    // The mention's own text
+    Keep,
+    // `@username`, falling back to the user's name
+    Username,
+    // The user's name, as `user_display_name` shows it
+    Name,
+}
+
+// Options for rendering a `MessageText` to a sink without Telegram formatting,
+// e.g. logs, emails or other chat platforms.
+#[derive(Clone, Debug)]
+pub struct PlainTextOptions {
+    inline_links: bool,
+    text_mentions: TextMentionStyle,
+    keep_spoilers: bool,
+    keep_code: bool,
+}
+
+impl Default for PlainTextOptions {
+    fn default() -> Self {
+        Self {
+            inline_links: true,
+            text_mentions: TextMentionStyle::Username,
+            keep_spoilers: true,
+            keep_code: true,
+        }
+    }
+}
+
+impl PlainTextOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Appends the target as `text (https://...)` to text links
+    pub fn inline_links(mut self, enable: bool) -> Self {
+        self.inline_links = enable;
+        self
+    }
+
+    pub fn text_mentions(mut self, style: TextMentionStyle) -> Self {
+        self.text_mentions = style;
+        self
+    }
+
+    pub fn keep_spoilers(mut self, enable: bool) -> Self {
+        self.keep_spoilers = enable;
+        self
+    }
+
+    pub fn keep_code(mut self, enable: bool) -> Self {
+        self.keep_code = enable;
+        self
+    }
+
+    fn is_dropped(&self, kind: &MessageEntityKind) -> bool {
+        match kind {
+            MessageEntityKind::Spoiler => !self.keep_spoilers,
+            MessageEntityKind::Code | MessageEntityKind::Pre { .. } => !self.keep_code,
+            _ => false,
+        }
+    }
+
+    // The text replacing the whole entity, if any
+    fn replacement(&self, kind: &MessageEntityKind) -> Option<String> {
+        match (kind, self.text_mentions) {
+            (MessageEntityKind::TextMention { user }, TextMentionStyle::Username) => {
+                Some(user.mention().unwrap_or_else(|| user_display_name(user)))
+            }
+            (MessageEntityKind::TextMention { user }, TextMentionStyle::Name) => {
+                Some(user_display_name(user))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl<'a> MessageText<'a> {
+    pub fn to_plain_text(&self, options: &PlainTextOptions) -> String {
+        let entities = self.entities();
+        let is_hidden = |entity: &MessageEntity| {
+            options.is_dropped(&entity.kind) || options.replacement(&entity.kind).is_some()
+        };
+        // Whether the text at `offset` is hidden by an entity other than `except`
+        let hidden_at = |offset: usize, except: Option<usize>| {
+            entities.iter().enumerate().any(|(index, entity)| {
+                Some(index) != except
+                    && is_hidden(entity)
+                    && entity.offset <= offset
+                    && offset < entity_end(entity)
+            })
+        };
+        let dropped_at = |offset: usize| {
+            entities.iter().any(|entity| {
+                options.is_dropped(&entity.kind)
+                    && entity.offset <= offset
+                    && offset < entity_end(entity)
+            })
+        };
+
+        let mut output = String::with_capacity(self.text.len());
+        let mut offset = 0;
+        let mut chars = self.text.chars();
+        // Whether text was dropped since the last char output other than
+        // whitespace, whose whitespace around it is then collapsed
+        let mut after_drop = false;
+
+        loop {
+            let ch = chars.next();
+            let end_of_text = ch.is_none();
+
+            // Link targets, innermost first
+            let mut closing: Vec<_> = entities
+                .iter()
+                .enumerate()
+                .filter(|(_, entity)| {
+                    let end = entity_end(entity);
+                    entity.length != 0 && (end == offset || (end_of_text && end > offset))
+                })
+                .collect();
+            closing.sort_by_key(|(_, entity)| std::cmp::Reverse(entity.offset));
+            for (index, entity) in closing {
+                if let MessageEntityKind::TextLink { url } = &entity.kind {
+                    let text = self.slice(entity.offset..entity_end(entity));
+                    if options.inline_links
+                        && !hidden_at(entity.offset, Some(index))
+                        && text.text().trim_end_matches('/') != url.as_str().trim_end_matches('/')
+                    {
+                        output.push_str(&format!(" ({url})"));
+                    }
+                }
+            }
+
+            let Some(ch) = ch else {
+                break;
+            };
+
+            for (index, entity) in entities.iter().enumerate() {
+                if entity.offset == offset
+                    && entity.length != 0
+                    && !options.is_dropped(&entity.kind)
+                {
+                    if let Some(replacement) = options.replacement(&entity.kind) {
+                        if !hidden_at(offset, Some(index)) {
+                            output.push_str(&replacement);
+                        }
+                    }
+                }
+            }
+
+            if dropped_at(offset) {
+                after_drop = true;
+            } else if !hidden_at(offset, None) {
+                let last = output.chars().next_back();
+                match last {
+                    _ if !after_drop || !ch.is_whitespace() => {
+                        after_drop = false;
+                        output.push(ch);
+                    }
+                    // Line breaks win over spaces
+                    Some(last) if last.is_whitespace() && ch == '\n' && last != '\n' => {
+                        output.pop();
+                        output.push(ch);
+                    }
+                    Some(last) if !last.is_whitespace() => output.push(ch),
+                    _ => {}
+                }
+            }
+            offset += ch.len_utf16();
+        }
+
+        if after_drop {
+            output.truncate(output.trim_end().len());
+        }
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use teloxide::types::{User, UserId};
+
+    use super::*;
+    use crate::text::mtb;
+
+    fn user(username: Option<&str>) -> User {
+        User {
+            id: UserId(42),
+            is_bot: false,
+            first_name: "Fat".into(),
+            last_name: Some("Cat".into()),
+            username: username.map(Into::into),
+            language_code: None,
+            is_premium: false,
+            added_to_attachment_menu: false,
+        }
+    }
+
+    fn fixture() -> MessageText<'static> {
+        mtb()
+            .plain("🐱 ")
+            .link(
+                mtb().plain("meow ").bold("cats"),
+                "https://http.cat".try_into().unwrap(),
+            )
+            .plain(", ")
+            .text_mention("kitty", &user(Some("cat")))
+            .plain(" and ")
+            .text_mention("喵", &user(None))
+            .plain(" ")
+            .spoiler(|| {
+                mtb()
+                    .plain("secret ")
+                    .link("here", "https://example.com".try_into().unwrap())
+            })
+            .plain(" ")
+            .code("code")
+            .plain(" ")
+            .link("https://http.cat", "https://http.cat".try_into().unwrap())
+            .build()
+    }
+
+    #[test]
+    fn to_plain_text() {
+        let msg_text = fixture();
+        assert_eq!(
+            msg_text.text(),
+            "🐱 meow cats, kitty and 喵 secret here code https://http.cat"
+        );
+
+        assert_eq!(
+            msg_text.to_plain_text(&PlainTextOptions::default()),
+            "🐱 meow cats (https://http.cat/), @cat and Fat Cat secret here (https://example.com/) \
+             code https://http.cat"
+        );
+        assert_eq!(
+            msg_text.to_plain_text(
+                &PlainTextOptions::new()
+                    .inline_links(false)
+                    .text_mentions(TextMentionStyle::Keep)
+            ),
+            msg_text.text()
+        );
+        assert_eq!(
+            msg_text.to_plain_text(
+                &PlainTextOptions::new()
+                    .text_mentions(TextMentionStyle::Name)
+                    .keep_spoilers(false)
+                    .keep_code(false)
+            ),
+            "🐱 meow cats (https://http.cat/), Fat Cat and Fat Cat https://http.cat"
+        );
+    }
+
+    #[test]
+    fn link_urls() {
+        let msg_text = mtb()
+            .link("cats", "https://http.cat".try_into().unwrap())
+            .plain(" at ")
+            .link("https://http.cat/", "https://http.cat".try_into().unwrap())
+            .build();
+
+        assert_eq!(
+            msg_text.to_plain_text(&PlainTextOptions::new()),
+            "cats (https://http.cat/) at https://http.cat/"
+        );
+        assert_eq!(
+            msg_text.to_plain_text(&PlainTextOptions::new().inline_links(false)),
+            "cats at https://http.cat/"
+        );
+    }
+
+    #[test]
+    fn text_mentions() {
+        let msg_text = mtb()
+            .text_mention("kitty", &user(Some("cat")))
+            .plain(" ")
+            .text_mention("喵", &user(None))
+            .build();
+        let to_plain_text =
+            |style| msg_text.to_plain_text(&PlainTextOptions::new().text_mentions(style));
+
+        assert_eq!(to_plain_text(TextMentionStyle::Keep), "kitty 喵");
+        assert_eq!(to_plain_text(TextMentionStyle::Username), "@cat Fat Cat");
+        assert_eq!(to_plain_text(TextMentionStyle::Name), "Fat Cat Fat Cat");
+    }
+
+    #[test]
+    fn code() {
+        let msg_text = mtb()
+            .plain("run ")
+            .code("meow")
+            .plain(":\n")
+            .pre("fn main() {\n    meow();\n}")
+            .plain("\ndone")
+            .build();
+
+        assert_eq!(
+            msg_text.to_plain_text(&PlainTextOptions::new()),
+            msg_text.text()
+        );
+        assert_eq!(
+            msg_text.to_plain_text(&PlainTextOptions::new().keep_code(false)),
+            "run :\ndone"
+        );
+    }
+
+    #[test]
+    fn spoilers() {
+        let msg_text = mtb()
+            .plain("the cat is ")
+            .spoiler("fat")
+            .plain(" and ")
+            .spoiler("cute")
+            .build();
+
+        assert_eq!(
+            msg_text.to_plain_text(&PlainTextOptions::new()),
+            msg_text.text()
+        );
+        assert_eq!(
+            msg_text.to_plain_text(&PlainTextOptions::new().keep_spoilers(false)),
+            "the cat is and"
+        );
+        assert_eq!(
+            mtb()
+                .spoiler("secret")
+                .plain(" a ")
+                .code("x")
+                .plain(" \n b ")
+                .spoiler("y")
+                .build()
+                .to_plain_text(
+                    &PlainTextOptions::new()
+                        .keep_spoilers(false)
+                        .keep_code(false)
+                ),
+            "a\nb"
+        );
+    }
+
+    #[test]
+    fn nested_entities() {
+        // A link inside a spoiler goes with it
+        let msg_text = mtb()
+            .spoiler(|| {
+                mtb()
+                    .plain("see ")
+                    .link("here", "https://example.com".try_into().unwrap())
+            })
+            .plain(" meow")
+            .build();
+        assert_eq!(
+            msg_text.to_plain_text(&PlainTextOptions::new()),
+            "see here (https://example.com/) meow"
+        );
+        assert_eq!(
+            msg_text.to_plain_text(&PlainTextOptions::new().keep_spoilers(false)),
+            "meow"
+        );
+
+        // The target follows the whole link text, formatting included
+        let msg_text = mtb()
+            .link(
+                mtb().bold("fat").plain(" ").italic("cats"),
+                "https://http.cat".try_into().unwrap(),
+            )
+            .build();
+        assert_eq!(
+            msg_text.to_plain_text(&PlainTextOptions::new()),
+            "fat cats (https://http.cat/)"
+        );
+
+        // A mention inside a link is replaced, the link's target kept
+        let msg_text = mtb()
+            .link(
+                mtb().plain("by ").text_mention("kitty", &user(Some("cat"))),
+                "https://http.cat".try_into().unwrap(),
+            )
+            .build();
+        assert_eq!(
+            msg_text.to_plain_text(&PlainTextOptions::new()),
+            "by @cat (https://http.cat/)"
+        );
+    }
+}
@@ -0,0 +1,181 @@
+use teloxide::types::LinkPreviewOptions;
+use url::Url;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PreviewMediaSize {
+    Small,
+    Large,
+}
+
+// How Telegram shows the preview of links in a message. By default, the first
+// link in the text is previewed below it.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LinkPreview {
+    pub disabled: bool,
+    // Previews this link instead of the first one in the text
+    pub url: Option<Url>,
+    // Only honored together with `url`, and if the preview supports it
+    pub media_size: Option<PreviewMediaSize>,
+    pub above_text: bool,
+}
+
+impl LinkPreview {
+    pub const fn new() -> Self {
+        Self {
+            disabled: false,
+            url: None,
+            media_size: None,
+            above_text: false,
+        }
+    }
+
+    pub fn disabled() -> Self {
+        Self {
+            disabled: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn url(url: Url) -> Self {
+        Self {
+            url: Some(url),
+            ..Default::default()
+        }
+    }
+
+    pub fn media_size(mut self, size: PreviewMediaSize) -> Self {
+        self.media_size = Some(size);
+        self
+    }
+
+    pub fn above_text(mut self, enable: bool) -> Self {
+        self.above_text = enable;
+        self
+    }
+}
+
+impl From<&LinkPreview> for LinkPreviewOptions {
+    fn from(value: &LinkPreview) -> Self {
+        Self {
+            is_disabled: value.disabled,
+            url: value.url.as_ref().map(|url| url.to_string()),
+            prefer_small_media: value.media_size == Some(PreviewMediaSize::Small),
+            prefer_large_media: value.media_size == Some(PreviewMediaSize::Large),
+            show_above_text: value.above_text,
+        }
+    }
+}
+
+impl TryFrom<LinkPreviewOptions> for LinkPreview {
+    type Error = url::ParseError;
+
+    fn try_from(value: LinkPreviewOptions) -> Result<Self, Self::Error> {
+        let media_size = if value.prefer_small_media {
+            Some(PreviewMediaSize::Small)
+        } else if value.prefer_large_media {
+            Some(PreviewMediaSize::Large)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            disabled: value.is_disabled,
+            url: value.url.as_deref().map(Url::parse).transpose()?,
+            media_size,
+            above_text: value.show_above_text,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn link_preview_options() {
+        let preview = LinkPreview::url("https://http.cat".try_into().unwrap())
+            .media_size(PreviewMediaSize::Large)
+            .above_text(true);
+
+        let options = LinkPreviewOptions::from(&preview);
+        assert_eq!(
+            options,
+            LinkPreviewOptions {
+                is_disabled: false,
+                url: Some("https://http.cat/".into()),
+                prefer_small_media: false,
+                prefer_large_media: true,
+                show_above_text: true,
+            }
+        );
+        assert_eq!(LinkPreview::try_from(options), Ok(preview));
+
+        assert!(LinkPreviewOptions::from(&LinkPreview::disabled()).is_disabled);
+        assert_eq!(LinkPreview::new(), LinkPreview::default());
+        assert_eq!(
+            LinkPreviewOptions::from(&LinkPreview::default()),
+            LinkPreviewOptions {
+                is_disabled: false,
+                url: None,
+                prefer_small_media: false,
+                prefer_large_media: false,
+                show_above_text: false,
+            }
+        );
+    }
+
+    #[test]
+    fn link_preview_options_fields() {
+        let options = |preview: LinkPreview| LinkPreviewOptions::from(&preview);
+        let default = options(LinkPreview::default());
+
+        assert_eq!(
+            options(LinkPreview::disabled()),
+            LinkPreviewOptions {
+                is_disabled: true,
+                ..default.clone()
+            }
+        );
+        assert_eq!(
+            options(LinkPreview::url("https://http.cat/404".try_into().unwrap())),
+            LinkPreviewOptions {
+                url: Some("https://http.cat/404".into()),
+                ..default.clone()
+            }
+        );
+        assert_eq!(
+            options(LinkPreview::default().media_size(PreviewMediaSize::Small)),
+            LinkPreviewOptions {
+                prefer_small_media: true,
+                ..default.clone()
+            }
+        );
+        assert_eq!(
+            options(LinkPreview::default().media_size(PreviewMediaSize::Large)),
+            LinkPreviewOptions {
+                prefer_large_media: true,
+                ..default.clone()
+            }
+        );
+        assert_eq!(
+            options(LinkPreview::default().above_text(true)),
+            LinkPreviewOptions {
+                show_above_text: true,
+                ..default.clone()
+            }
+        );
+
+        assert_eq!(
+            LinkPreview::try_from(LinkPreviewOptions {
+                prefer_small_media: true,
+                ..default.clone()
+            }),
+            Ok(LinkPreview::default().media_size(PreviewMediaSize::Small))
+        );
+        assert!(LinkPreview::try_from(LinkPreviewOptions {
+            url: Some("cat".into()),
+            ..default
+        })
+        .is_err());
+    }
+}
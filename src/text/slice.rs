@@ -0,0 +1,204 @@
+use std::ops::{Bound, Range, RangeBounds};
+
+use teloxide::types::MessageEntity;
+
+use super::{entity_end, is_atomic, MessageText};
+
+impl<'a> MessageText<'a> {
+    // The range is in UTF-16 code units. Bounds falling inside a surrogate pair
+    // are moved inwards, so that no character is ever cut in half.
+    pub fn slice(&self, range: impl RangeBounds<usize>) -> MessageText<'static> {
+        let (bytes, Range { start, end }) = self.resolve_range(range);
+
+        let entities: Vec<_> = self
+            .entities
+            .iter()
+            .filter_map(|entity| {
+                let entity_end = entity_end(entity);
+                let clipped = (entity.offset.max(start), entity_end.min(end));
+                let is_clipped = clipped != (entity.offset, entity_end);
+
+                (clipped.0 < clipped.1 && !(is_clipped && is_atomic(&entity.kind))).then(|| {
+                    MessageEntity {
+                        kind: entity.kind.clone(),
+                        offset: clipped.0 - start,
+                        length: clipped.1 - clipped.0,
+                    }
+                })
+            })
+            .collect();
+
+        MessageText {
+            text: self.text[bytes].to_owned().into(),
+            entities: entities.into(),
+            link_preview: self.link_preview.clone(),
+        }
+    }
+
+    // Cuts the text down to at most `max_len` UTF-16 code units, ending with
+    // `ellipsis` if anything was cut.
+    pub fn truncate_with_ellipsis(
+        &self,
+        max_len: usize,
+        ellipsis: impl AsRef<str>,
+    ) -> MessageText<'static> {
+        let ellipsis = ellipsis.as_ref();
+        let ellipsis_len = ellipsis.encode_utf16().count();
+
+        if self.text.encode_utf16().count() <= max_len {
+            self.slice(..)
+        } else if max_len < ellipsis_len {
+            self.slice(..max_len)
+        } else {
+            let mut truncated = self.slice(..max_len - ellipsis_len);
+            truncated.append_text(ellipsis);
+            truncated
+        }
+    }
+
+    // Resolves a range in UTF-16 code units to the (bytes, UTF-16 code units)
+    // ranges of the chars it fully covers. An empty result is placed at the
+    // first char boundary after the start.
+    pub(super) fn resolve_range(
+        &self,
+        range: impl RangeBounds<usize>,
+    ) -> (Range<usize>, Range<usize>) {
+        // Clamped to the text, `..=usize::MAX` included
+        let text_len = self.text.encode_utf16().count();
+        let start = match range.start_bound() {
+            Bound::Included(&start) => start,
+            Bound::Excluded(&start) => start.checked_add(1).unwrap_or(text_len),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&end) => end.checked_add(1).unwrap_or(text_len),
+            Bound::Excluded(&end) => end,
+            Bound::Unbounded => text_len,
+        }
+        .min(text_len);
+
+        // (byte, UTF-16) positions of all char boundaries
+        let boundaries = self.text.chars().scan((0, 0), |(index, offset), ch| {
+            *index += ch.len_utf8();
+            *offset += ch.len_utf16();
+            Some((*index, *offset))
+        });
+        let boundaries = std::iter::once((0, 0)).chain(boundaries);
+
+        let (start_index, start) = boundaries
+            .clone()
+            .find(|&(_, offset)| offset >= start)
+            .unwrap_or_else(|| boundaries.clone().last().unwrap());
+        let (end_index, end) = boundaries
+            .take_while(|&(_, offset)| offset <= end)
+            .last()
+            .filter(|&(index, _)| index >= start_index)
+            .unwrap_or((start_index, start));
+
+        (start_index..end_index, start..end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use teloxide::types::MessageEntityKind;
+
+    use super::*;
+    use crate::text::mtb;
+
+    fn fixture() -> MessageText<'static> {
+        let mut msg_text = mtb()
+            .plain("🐱 ")
+            .bold(mtb().plain("meow ").italic("喵呜"))
+            .plain(" ")
+            .build();
+        msg_text.append_text_with_entity("https://http.cat", MessageEntityKind::Url);
+        msg_text
+    }
+
+    #[test]
+    fn slice() {
+        let msg_text = fixture();
+        assert_eq!(msg_text.text(), "🐱 meow 喵呜 https://http.cat");
+
+        assert_eq!(msg_text.slice(..), msg_text);
+        assert_eq!(msg_text.slice(100..), MessageText::from(""));
+        assert_eq!(msg_text.slice(..=usize::MAX), msg_text);
+        assert_eq!(
+            msg_text.slice(3..=usize::MAX).text(),
+            "meow 喵呜 https://http.cat"
+        );
+        assert_eq!(
+            msg_text.slice((Bound::Excluded(usize::MAX), Bound::Unbounded)),
+            MessageText::from("")
+        );
+
+        let sliced = msg_text.slice(5..9);
+        assert_eq!(sliced.text(), "ow 喵");
+        assert_eq!(
+            sliced.entities(),
+            vec![
+                MessageEntity {
+                    kind: MessageEntityKind::Bold,
+                    offset: 0,
+                    length: 4,
+                },
+                MessageEntity {
+                    kind: MessageEntityKind::Italic,
+                    offset: 3,
+                    length: 1,
+                }
+            ]
+        );
+
+        // Surrogate pairs are never cut
+        assert_eq!(msg_text.slice(1..4).text(), " m");
+        assert_eq!(msg_text.slice(0..=0).text(), "");
+
+        // Atomic entities are dropped once cut
+        let sliced = msg_text.slice(11..20);
+        assert_eq!(sliced.text(), "https://h");
+        assert!(sliced.entities().is_empty());
+        let sliced = msg_text.slice(11..);
+        assert_eq!(
+            sliced.entities(),
+            vec![MessageEntity {
+                kind: MessageEntityKind::Url,
+                offset: 0,
+                length: 16,
+            }]
+        );
+    }
+
+    #[test]
+    fn truncate_with_ellipsis() {
+        let msg_text = fixture();
+
+        assert_eq!(msg_text.truncate_with_ellipsis(100, "…"), msg_text);
+
+        let truncated = msg_text.truncate_with_ellipsis(10, "…");
+        assert_eq!(truncated.text(), "🐱 meow 喵…");
+        assert_eq!(
+            truncated.entities(),
+            vec![
+                MessageEntity {
+                    kind: MessageEntityKind::Bold,
+                    offset: 3,
+                    length: 6,
+                },
+                MessageEntity {
+                    kind: MessageEntityKind::Italic,
+                    offset: 8,
+                    length: 1,
+                }
+            ]
+        );
+
+        let truncated = msg_text.truncate_with_ellipsis(3, "...");
+        assert_eq!(truncated.text(), "...");
+        assert!(truncated.entities().is_empty());
+
+        let truncated = msg_text.truncate_with_ellipsis(2, "...");
+        assert_eq!(truncated.text(), "🐱");
+    }
+}
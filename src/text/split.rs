@@ -0,0 +1,276 @@
+use teloxide::types::MessageEntityKind;
+
+use super::{entity_end, MessageText};
+
+pub const MESSAGE_TEXT_LIMIT: usize = 4096;
+pub const CAPTION_LIMIT: usize = 1024;
+
+impl<'a> MessageText<'a> {
+    // Splits the text into chunks of at most `limit` UTF-16 code units,
+    // preferring paragraph, line and word boundaries in that order. The
+    // separator at a boundary is dropped, unless it's inside a code or pre
+    // entity where it's kept at the end of the chunk, and entities straddling a
+    // boundary are split into one entity per chunk, like `slice` does. Like
+    // there too, a cut URL, mention, hashtag or other atomic entity is dropped
+    // instead, as its parts would mean something else.
+    //
+    // A single character longer than `limit` is still emitted as its own chunk.
+    // Chunks left empty or whitespace-only are skipped, Telegram rejects them.
+    pub fn split(&self, limit: usize) -> impl Iterator<Item = MessageText<'static>> + '_ {
+        // (UTF-16 offset, char) of every char
+        let chars: Vec<(usize, char)> = self
+            .text
+            .chars()
+            .scan(0, |offset, ch| {
+                let current = *offset;
+                *offset += ch.len_utf16();
+                Some((current, ch))
+            })
+            .collect();
+        let text_len = chars
+            .last()
+            .map_or(0, |(offset, ch)| offset + ch.len_utf16());
+
+        let mut start_index = 0;
+
+        std::iter::from_fn(move || {
+            let &(start, _) = chars.get(start_index)?;
+
+            if text_len - start <= limit {
+                start_index = chars.len();
+                return Some(self.slice(start..text_len));
+            }
+
+            // Index of the first char which doesn't fit into this chunk
+            let overflow_index = start_index
+                + chars[start_index..]
+                    .iter()
+                    .position(|(offset, ch)| offset + ch.len_utf16() - start > limit)
+                    .unwrap()
+                    .max(1);
+            let window = &chars[start_index..overflow_index];
+
+            let (end, next_index) = [
+                find_boundary(window, limit, 2, |window| {
+                    matches!(window, [(_, '\n'), (_, '\n'), ..])
+                }),
+                find_boundary(window, limit, 1, |window| matches!(window, [(_, '\n'), ..])),
+                find_boundary(
+                    window,
+                    limit,
+                    1,
+                    |window| matches!(window, [(_, ch), ..] if ch.is_whitespace()),
+                ),
+            ]
+            .into_iter()
+            .flatten()
+            .next()
+            .map(|(boundary, separator_len)| {
+                let next_index = start_index + boundary + separator_len;
+                let offset = window[boundary].0;
+                let end = match self.is_code_at(offset) {
+                    true => chars
+                        .get(next_index)
+                        .map_or(text_len, |(offset, _)| *offset),
+                    false => offset,
+                };
+                (end, next_index)
+            })
+            .unwrap_or_else(|| {
+                let end = chars
+                    .get(overflow_index)
+                    .map_or(text_len, |(offset, _)| *offset);
+                (end, overflow_index)
+            });
+
+            start_index = next_index;
+            Some(self.slice(start..end))
+        })
+        .filter(|chunk| !chunk.text().trim().is_empty())
+    }
+
+    fn is_code_at(&self, offset: usize) -> bool {
+        self.entities.iter().any(|entity| {
+            matches!(
+                entity.kind,
+                MessageEntityKind::Code | MessageEntityKind::Pre { .. }
+            ) && entity.offset <= offset
+                && offset < entity_end(entity)
+        })
+    }
+}
+
+// Finds the last separator of `separator_len` chars in the second half of the
+// window, returning its index and length.
+fn find_boundary(
+    window: &[(usize, char)],
+    limit: usize,
+    separator_len: usize,
+    is_separator: impl Fn(&[(usize, char)]) -> bool,
+) -> Option<(usize, usize)> {
+    let start = window[0].0;
+
+    (1..window.len())
+        .rev()
+        .take_while(|&index| window[index].0 - start >= limit / 2)
+        .find(|&index| is_separator(&window[index..]))
+        .map(|index| (index, separator_len))
+}
+
+#[cfg(test)]
+mod tests {
+    use teloxide::types::MessageEntity;
+
+    use super::*;
+    use crate::text::mtb;
+
+    fn texts<'a>(chunks: &'a [MessageText]) -> Vec<&'a str> {
+        chunks.iter().map(|chunk| chunk.text()).collect()
+    }
+
+    #[test]
+    fn split_on_boundaries() {
+        let msg_text = MessageText::from("aaaa bbbb\ncccc\n\ndddd eeee");
+
+        let chunks: Vec<_> = msg_text.split(100).collect();
+        assert_eq!(texts(&chunks), vec!["aaaa bbbb\ncccc\n\ndddd eeee"]);
+
+        let chunks: Vec<_> = msg_text.split(20).collect();
+        assert_eq!(texts(&chunks), vec!["aaaa bbbb\ncccc", "dddd eeee"]);
+
+        let chunks: Vec<_> = msg_text.split(12).collect();
+        assert_eq!(texts(&chunks), vec!["aaaa bbbb", "cccc\n\ndddd", "eeee"]);
+
+        let chunks: Vec<_> = msg_text.split(6).collect();
+        assert_eq!(texts(&chunks), vec!["aaaa", "bbbb", "cccc", "dddd", "eeee"]);
+
+        let chunks: Vec<_> = msg_text.split(3).collect();
+        assert_eq!(
+            texts(&chunks),
+            vec!["aaa", "a", "bbb", "b", "ccc", "c", "ddd", "d", "eee", "e"]
+        );
+
+        assert_eq!(MessageText::from("").split(10).count(), 0);
+    }
+
+    #[test]
+    fn split_skips_blank_chunks() {
+        let msg_text = MessageText::from("aaaa          bbbb\n\n\n\n      ");
+
+        let chunks: Vec<_> = msg_text.split(5).collect();
+        assert_eq!(texts(&chunks), vec!["aaaa", "bbbb"]);
+
+        let chunks: Vec<_> = msg_text.split(3).collect();
+        assert!(chunks.iter().all(|chunk| !chunk.text().trim().is_empty()));
+
+        assert_eq!(MessageText::from(" \n \n ").split(1).count(), 0);
+    }
+
+    #[test]
+    fn split_keeps_indentation() {
+        let msg_text = MessageText::from("fn main() {\n    meow();\n}");
+        let chunks: Vec<_> = msg_text.split(20).collect();
+        assert_eq!(texts(&chunks), vec!["fn main() {", "    meow();\n}"]);
+
+        let msg_text = mtb()
+            .plain("Code:\n")
+            .pre("fn main() {\n    meow();\n    purr();\n}")
+            .build();
+        let chunks: Vec<_> = msg_text.split(24).collect();
+        assert_eq!(
+            texts(&chunks),
+            vec!["Code:\nfn main() {\n", "    meow();\n    purr();\n", "}"]
+        );
+        assert_eq!(
+            chunks
+                .iter()
+                .map(|chunk| chunk.entities())
+                .collect::<Vec<_>>(),
+            vec![
+                vec![MessageEntity {
+                    kind: MessageEntityKind::Pre { language: None },
+                    offset: 6,
+                    length: 12,
+                }],
+                vec![MessageEntity {
+                    kind: MessageEntityKind::Pre { language: None },
+                    offset: 0,
+                    length: 24,
+                }],
+                vec![MessageEntity {
+                    kind: MessageEntityKind::Pre { language: None },
+                    offset: 0,
+                    length: 1,
+                }],
+            ]
+        );
+    }
+
+    #[test]
+    fn split_surrogate_pairs() {
+        let msg_text = MessageText::from("🐱🐱🐱");
+
+        let chunks: Vec<_> = msg_text.split(3).collect();
+        assert_eq!(texts(&chunks), vec!["🐱", "🐱", "🐱"]);
+
+        let chunks: Vec<_> = msg_text.split(1).collect();
+        assert_eq!(texts(&chunks), vec!["🐱", "🐱", "🐱"]);
+    }
+
+    #[test]
+    fn split_entities() {
+        let msg_text = mtb()
+            .plain("🐱 ")
+            .bold("meow meow")
+            .plain(" ")
+            .italic("喵")
+            .disable_preview()
+            .build();
+
+        let chunks: Vec<_> = msg_text.split(8).collect();
+        assert_eq!(texts(&chunks), vec!["🐱 meow", "meow 喵"]);
+        assert_eq!(
+            chunks[0].entities(),
+            vec![MessageEntity {
+                kind: MessageEntityKind::Bold,
+                offset: 3,
+                length: 4,
+            }]
+        );
+        assert_eq!(
+            chunks[1].entities(),
+            vec![
+                MessageEntity {
+                    kind: MessageEntityKind::Bold,
+                    offset: 0,
+                    length: 4,
+                },
+                MessageEntity {
+                    kind: MessageEntityKind::Italic,
+                    offset: 5,
+                    length: 1,
+                }
+            ]
+        );
+        assert!(chunks.iter().all(|chunk| chunk.disable_preview()));
+
+        // Atomic entities straddling a boundary are dropped
+        let msg_text = mtb()
+            .mention("@meowmeow")
+            .plain(" ")
+            .url("https://http.cat")
+            .build();
+        let chunks: Vec<_> = msg_text.split(12).collect();
+        assert_eq!(texts(&chunks), vec!["@meowmeow", "https://http", ".cat"]);
+        assert_eq!(
+            chunks[0].entities(),
+            vec![MessageEntity {
+                kind: MessageEntityKind::Mention,
+                offset: 0,
+                length: 9,
+            }]
+        );
+        assert!(chunks[1].entities().is_empty());
+        assert!(chunks[2].entities().is_empty());
+    }
+}
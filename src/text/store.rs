@@ -0,0 +1,260 @@
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use serde_json as json;
+use teloxide::types::{LinkPreviewOptions, MessageEntity};
+
+use super::{LinkPreview, MessageText};
+use crate::{error::*, DbPoolCallback};
+
+// The stored JSON shape, bump the version on any incompatible change:
+//
+// {
+//     "version": 1,
+//     "text": "meow",
+//     "entities": [{ "type": "bold", "offset": 0, "length": 4 }],
+//     "link_preview": { "is_disabled": true }
+// }
+//
+// `entities` and `link_preview` are Bot API `MessageEntity` and
+// `LinkPreviewOptions` objects, entities having UTF-16 offsets. Both may be
+// omitted, `link_preview` being left unset then.
+const FORMAT_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+struct StoredRef<'a> {
+    version: u32,
+    text: &'a str,
+    entities: &'a [MessageEntity],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    link_preview: Option<LinkPreviewOptions>,
+}
+
+#[derive(Deserialize)]
+struct Stored {
+    version: u32,
+    text: String,
+    #[serde(default)]
+    entities: Vec<MessageEntity>,
+    #[serde(default)]
+    link_preview: Option<LinkPreviewOptions>,
+}
+
+impl<'a> Serialize for MessageText<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        StoredRef {
+            version: FORMAT_VERSION,
+            text: &self.text,
+            entities: &self.entities,
+            link_preview: self.link_preview.as_ref().map(Into::into),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for MessageText<'static> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let stored = Stored::deserialize(deserializer)?;
+        if stored.version != FORMAT_VERSION {
+            return Err(de::Error::custom(format!(
+                "unsupported MessageText format version {}, expected {FORMAT_VERSION}",
+                stored.version
+            )));
+        }
+
+        let link_preview = stored
+            .link_preview
+            .map(LinkPreview::try_from)
+            .transpose()
+            .map_err(de::Error::custom)?;
+
+        Ok(MessageText {
+            text: stored.text.into(),
+            entities: stored.entities.into(),
+            link_preview,
+        })
+    }
+}
+
+impl<'a> MessageText<'a> {
+    pub fn to_json(&self) -> Result<String> {
+        Ok(json::to_string(self)?)
+    }
+
+    pub fn from_json(data: impl AsRef<str>) -> Result<MessageText<'static>> {
+        Ok(json::from_str(data.as_ref())?)
+    }
+
+    // Replaces the text saved under `key` if any
+    pub async fn save(&self, db_pool: impl DbPoolCallback<'_>, key: impl AsRef<str>) -> Result<()> {
+        let key = key.as_ref();
+        let text_json = self.to_json()?;
+
+        sqlx::query!(
+            r#"
+INSERT OR REPLACE INTO telegram_message_text ( key, text_json )
+VALUES ( ?1, ?2 )
+        "#,
+            key,
+            text_json
+        )
+        .execute(db_pool())
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn load(
+        db_pool: impl DbPoolCallback<'_>,
+        key: impl AsRef<str>,
+    ) -> Result<Option<MessageText<'static>>> {
+        let key = key.as_ref();
+
+        let record = sqlx::query!(
+            r#"
+SELECT text_json
+FROM telegram_message_text
+WHERE key = ?1
+        "#,
+            key,
+        )
+        .fetch_optional(db_pool())
+        .await?;
+
+        record
+            .map(|r| MessageText::from_json(r.text_json))
+            .transpose()
+    }
+
+    // Returns whether a text was saved under `key`
+    pub async fn delete_saved(
+        db_pool: impl DbPoolCallback<'_>,
+        key: impl AsRef<str>,
+    ) -> Result<bool> {
+        let key = key.as_ref();
+
+        let result = sqlx::query!(
+            r#"
+DELETE FROM telegram_message_text
+WHERE key = ?1
+        "#,
+            key,
+        )
+        .execute(db_pool())
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    use super::*;
+    use crate::text::mtb;
+
+    #[test]
+    fn json_shape() {
+        let msg_text = mtb()
+            .bold("🐱 meow")
+            .plain(" ")
+            .link("喵", "https://http.cat".try_into().unwrap())
+            .disable_preview()
+            .build();
+
+        let data = msg_text.to_json().unwrap();
+        assert_eq!(
+            json::from_str::<json::Value>(&data).unwrap(),
+            json::json!({
+                "version": 1,
+                "text": "🐱 meow 喵",
+                "entities": [
+                    { "type": "bold", "offset": 0, "length": 7 },
+                    { "type": "text_link", "offset": 8, "length": 1, "url": "https://http.cat/" },
+                ],
+                "link_preview": { "is_disabled": true },
+            })
+        );
+        assert_eq!(MessageText::from_json(&data).unwrap(), msg_text);
+    }
+
+    #[test]
+    fn from_json() {
+        assert_eq!(
+            MessageText::from_json(r#"{ "version": 1, "text": "meow" }"#).unwrap(),
+            MessageText::from("meow")
+        );
+        assert_eq!(
+            MessageText::from_json(
+                r#"{ "version": 1, "text": "meow", "link_preview": { "url": "https://http.cat", "show_above_text": true } }"#
+            )
+            .unwrap()
+            .link_preview(),
+            &LinkPreview::url("https://http.cat".try_into().unwrap()).above_text(true)
+        );
+        assert!(MessageText::from_json(
+            r#"{ "version": 1, "text": "meow", "link_preview": { "url": "cat" } }"#
+        )
+        .is_err());
+        assert!(MessageText::from_json(r#"{ "version": 2, "text": "meow" }"#).is_err());
+        assert!(MessageText::from_json(r#"{ "text": "meow" }"#).is_err());
+    }
+
+    #[test]
+    fn unset_link_preview() {
+        let msg_text = MessageText::from("meow");
+        let data = msg_text.to_json().unwrap();
+        assert_eq!(
+            json::from_str::<json::Value>(&data).unwrap(),
+            json::json!({ "version": 1, "text": "meow", "entities": [] })
+        );
+        assert!(!MessageText::from_json(&data).unwrap().is_link_preview_set());
+    }
+
+    #[test]
+    fn into_owned() {
+        let text = String::from("meow");
+        let msg_text = MessageText::from(&text).into_owned();
+        drop(text);
+        assert_eq!(msg_text.text(), "meow");
+    }
+
+    #[test]
+    fn save_and_load() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+
+        runtime.block_on(async {
+            // A single connection, each one having its own in-memory database
+            let db_pool = SqlitePoolOptions::new()
+                .max_connections(1)
+                .idle_timeout(None)
+                .max_lifetime(None)
+                .connect("sqlite::memory:")
+                .await
+                .unwrap();
+            sqlx::migrate!().run(&db_pool).await.unwrap();
+            let db_pool = || &db_pool;
+
+            let msg_text = mtb().bold("meow").disable_preview().build();
+            msg_text.save(db_pool, "draft").await.unwrap();
+            assert_eq!(
+                MessageText::load(db_pool, "draft").await.unwrap(),
+                Some(msg_text)
+            );
+            assert_eq!(MessageText::load(db_pool, "post").await.unwrap(), None);
+
+            let msg_text = MessageText::from("喵");
+            msg_text.save(db_pool, "draft").await.unwrap();
+            assert_eq!(
+                MessageText::load(db_pool, "draft").await.unwrap(),
+                Some(msg_text)
+            );
+
+            assert!(MessageText::delete_saved(db_pool, "draft").await.unwrap());
+            assert!(!MessageText::delete_saved(db_pool, "draft").await.unwrap());
+            assert_eq!(MessageText::load(db_pool, "draft").await.unwrap(), None);
+        });
+    }
+}
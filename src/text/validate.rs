@@ -0,0 +1,345 @@
+use std::cmp::Reverse;
+
+use teloxide::types::MessageEntity;
+
+use super::{entity_end, is_atomic, MessageText};
+
+#[derive(thiserror::Error, Clone, Debug, PartialEq, Eq)]
+pub enum EntityError {
+    #[error("entity #{index} is empty")]
+    Empty { index: usize },
+
+    #[error("entity #{index} ends at {end}, beyond the text length {text_len}")]
+    OutOfRange {
+        index: usize,
+        end: usize,
+        text_len: usize,
+    },
+
+    #[error("entity #{index} starts or ends inside a surrogate pair")]
+    SplitsSurrogatePair { index: usize },
+
+    #[error("entity #{index} is not sorted after its predecessor")]
+    Unsorted { index: usize },
+}
+
+// Telegram expects entities sorted by offset, with outer ones first
+fn sort_key(entity: &MessageEntity) -> (usize, Reverse<usize>) {
+    (entity.offset, Reverse(entity.length))
+}
+
+impl<'a> MessageText<'a> {
+    pub fn validate(&self) -> Result<(), Vec<EntityError>> {
+        let text_len = self.text.encode_utf16().count();
+        let inside_pairs = self.inside_surrogate_pairs();
+
+        let errors: Vec<_> = self
+            .entities
+            .iter()
+            .enumerate()
+            .flat_map(|(index, entity)| {
+                // Beyond any text if it overflows
+                let end = entity.offset.checked_add(entity.length);
+                let out_of_range = end.is_none_or(|end| end > text_len);
+
+                [
+                    (entity.length == 0).then_some(EntityError::Empty { index }),
+                    out_of_range.then_some(EntityError::OutOfRange {
+                        index,
+                        end: end.unwrap_or(usize::MAX),
+                        text_len,
+                    }),
+                    (inside_pairs.binary_search(&entity.offset).is_ok()
+                        || end.is_some_and(|end| inside_pairs.binary_search(&end).is_ok()))
+                    .then_some(EntityError::SplitsSurrogatePair { index }),
+                    (index > 0 && sort_key(&self.entities[index - 1]) > sort_key(entity))
+                        .then_some(EntityError::Unsorted { index }),
+                ]
+                .into_iter()
+                .flatten()
+            })
+            .collect();
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    // Clamps entities into the text and out of surrogate pairs, drops empty
+    // ones, merges adjacent or overlapping identical ones and sorts the result.
+    pub fn normalize(&mut self) {
+        let text_len = self.text.encode_utf16().count();
+        let inside_pairs = self.inside_surrogate_pairs();
+        let clamp = |offset: usize, extend: bool| {
+            let offset = offset.min(text_len);
+            match inside_pairs.binary_search(&offset) {
+                Ok(_) if extend => offset + 1,
+                Ok(_) => offset - 1,
+                Err(_) => offset,
+            }
+        };
+
+        let mut entities: Vec<_> = self
+            .entities
+            .iter()
+            .filter_map(|entity| {
+                let start = clamp(entity.offset, false);
+                let end = clamp(entity_end(entity), true);
+                (start < end).then(|| MessageEntity {
+                    kind: entity.kind.clone(),
+                    offset: start,
+                    length: end - start,
+                })
+            })
+            .collect();
+        entities.sort_by_key(sort_key);
+
+        let mut merged: Vec<MessageEntity> = Vec::with_capacity(entities.len());
+        for entity in entities {
+            let mergeable = merged.iter_mut().rev().find(|merged| {
+                merged.kind == entity.kind
+                    && !is_atomic(&entity.kind)
+                    && entity_end(merged) >= entity.offset
+            });
+            match mergeable {
+                Some(merged) => {
+                    let end = entity_end(merged).max(entity_end(&entity));
+                    merged.length = end - merged.offset;
+                }
+                None => merged.push(entity),
+            }
+        }
+        merged.sort_by_key(sort_key);
+
+        self.entities = merged.into();
+    }
+
+    // Sorted UTF-16 offsets pointing between the two halves of a surrogate pair
+    fn inside_surrogate_pairs(&self) -> Vec<usize> {
+        self.text
+            .chars()
+            .scan(0, |offset, ch| {
+                *offset += ch.len_utf16();
+                Some((*offset, ch.len_utf16()))
+            })
+            .filter(|&(_, len)| len == 2)
+            .map(|(end, _)| end - 1)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+    use teloxide::types::MessageEntityKind;
+
+    use super::*;
+    use crate::text::PlainTextOptions;
+
+    fn entity(kind: MessageEntityKind, offset: usize, length: usize) -> MessageEntity {
+        MessageEntity {
+            kind,
+            offset,
+            length,
+        }
+    }
+
+    #[test]
+    fn validate() {
+        let msg_text = MessageText::new(
+            "🐱 meow",
+            vec![
+                entity(MessageEntityKind::Bold, 0, 7),
+                entity(MessageEntityKind::Italic, 3, 0),
+                entity(MessageEntityKind::Code, 3, 5),
+                entity(MessageEntityKind::Underline, 1, 2),
+                entity(MessageEntityKind::Spoiler, 0, 2),
+                entity(MessageEntityKind::Strikethrough, 3, 4),
+            ],
+        );
+
+        assert_eq!(
+            msg_text.validate(),
+            Err(vec![
+                EntityError::Empty { index: 1 },
+                EntityError::OutOfRange {
+                    index: 2,
+                    end: 8,
+                    text_len: 7
+                },
+                EntityError::Unsorted { index: 2 },
+                EntityError::SplitsSurrogatePair { index: 3 },
+                EntityError::Unsorted { index: 3 },
+                EntityError::Unsorted { index: 4 },
+            ])
+        );
+        assert_eq!(MessageText::from("meow").validate(), Ok(()));
+        assert_eq!(
+            MessageText::new("meow", vec![entity(MessageEntityKind::Bold, 2, usize::MAX)])
+                .validate(),
+            Err(vec![EntityError::OutOfRange {
+                index: 0,
+                end: usize::MAX,
+                text_len: 4
+            }])
+        );
+    }
+
+    #[test]
+    fn normalize() {
+        let mut msg_text = MessageText::new(
+            "🐱 meow @cat@cat",
+            vec![
+                entity(MessageEntityKind::Bold, 5, 2),
+                entity(MessageEntityKind::Bold, 3, 2),
+                entity(MessageEntityKind::Italic, 3, 0),
+                entity(MessageEntityKind::Code, 1, 100),
+                entity(MessageEntityKind::Mention, 8, 4),
+                entity(MessageEntityKind::Mention, 12, 4),
+            ],
+        );
+        msg_text.normalize();
+
+        assert_eq!(
+            msg_text.entities(),
+            vec![
+                entity(MessageEntityKind::Code, 0, 16),
+                entity(MessageEntityKind::Bold, 3, 4),
+                entity(MessageEntityKind::Mention, 8, 4),
+                entity(MessageEntityKind::Mention, 12, 4),
+            ]
+        );
+        assert_eq!(msg_text.validate(), Ok(()));
+    }
+
+    fn arb_msg_text() -> impl Strategy<Value = MessageText<'static>> {
+        let kind = prop_oneof![
+            Just(MessageEntityKind::Bold),
+            Just(MessageEntityKind::Italic),
+            Just(MessageEntityKind::Code),
+            Just(MessageEntityKind::Url),
+        ];
+        (
+            "[a 喵🐱\n]{0,20}",
+            prop::collection::vec((kind, 0..30usize, 0..30usize), 0..10),
+        )
+            .prop_map(|(text, entities)| {
+                let entities: Vec<_> = entities
+                    .into_iter()
+                    .map(|(kind, offset, length)| entity(kind, offset, length))
+                    .collect();
+                MessageText::new(text, entities)
+            })
+    }
+
+    // Offsets and lengths near `usize::MAX`, whose sums overflow
+    fn arb_hostile_msg_text() -> impl Strategy<Value = MessageText<'static>> {
+        let kind = prop_oneof![
+            Just(MessageEntityKind::Bold),
+            Just(MessageEntityKind::Code),
+            Just(MessageEntityKind::Url),
+            Just(MessageEntityKind::TextLink {
+                url: "https://http.cat".parse().unwrap()
+            }),
+        ];
+        let number = prop_oneof![0..30usize, usize::MAX - 30..=usize::MAX];
+        (
+            "[a 喵🐱\n]{0,20}",
+            prop::collection::vec((kind, number.clone(), number), 0..10),
+        )
+            .prop_map(|(text, entities)| {
+                let entities: Vec<_> = entities
+                    .into_iter()
+                    .map(|(kind, offset, length)| entity(kind, offset, length))
+                    .collect();
+                MessageText::new(text, entities)
+            })
+    }
+
+    // Kinds of non-atomic entities covering each UTF-16 code unit
+    fn formatting(msg_text: &MessageText) -> Vec<Vec<MessageEntityKind>> {
+        (0..msg_text.text().encode_utf16().count())
+            .map(|offset| {
+                let mut kinds: Vec<_> = msg_text
+                    .entities()
+                    .iter()
+                    .filter(|entity| {
+                        !is_atomic(&entity.kind)
+                            && entity.offset <= offset
+                            && offset < entity_end(entity)
+                    })
+                    .map(|entity| entity.kind.clone())
+                    .collect();
+                kinds.sort_by_key(|kind| format!("{kind:?}"));
+                kinds.dedup();
+                kinds
+            })
+            .collect()
+    }
+
+    proptest! {
+        #[test]
+        fn normalized_is_valid(mut msg_text in arb_msg_text()) {
+            msg_text.normalize();
+            prop_assert_eq!(msg_text.validate(), Ok(()));
+        }
+
+        #[test]
+        fn hostile_entities(msg_text in arb_hostile_msg_text()) {
+            let overflowing = msg_text
+                .entities()
+                .iter()
+                .filter(|entity| entity.offset.checked_add(entity.length).is_none())
+                .count();
+            let out_of_range = msg_text
+                .validate()
+                .err()
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|error| matches!(error, EntityError::OutOfRange { .. }))
+                .count();
+            prop_assert!(out_of_range >= overflowing);
+
+            // None of these may panic
+            msg_text.slice(1..);
+            msg_text.to_html();
+            msg_text.to_markdown_v2();
+            msg_text.to_plain_text(&PlainTextOptions::new().inline_links(true));
+            msg_text.extract_semantics();
+            msg_text.split(5).for_each(drop);
+            let mut edited = msg_text.clone();
+            edited.insert(0, "🐱");
+            edited.remove(..3);
+            edited.append(msg_text.clone());
+
+            let mut normalized = msg_text;
+            normalized.normalize();
+            prop_assert_eq!(normalized.validate(), Ok(()));
+        }
+
+        #[test]
+        fn normalize_is_idempotent(mut msg_text in arb_msg_text()) {
+            msg_text.normalize();
+            let normalized = msg_text.clone();
+            msg_text.normalize();
+            prop_assert_eq!(msg_text, normalized);
+        }
+
+        #[test]
+        fn normalize_keeps_formatting(msg_text in arb_msg_text()) {
+            // Only holds if no entity starts or ends inside a surrogate pair
+            prop_assume!(!msg_text
+                .validate()
+                .err()
+                .unwrap_or_default()
+                .iter()
+                .any(|error| matches!(error, EntityError::SplitsSurrogatePair { .. })));
+
+            let mut normalized = msg_text.clone();
+            normalized.normalize();
+            prop_assert_eq!(formatting(&normalized), formatting(&msg_text));
+        }
+    }
+}
@@ -0,0 +1,11 @@
+use tgbot_utils::cmd_arg::Args;
+
+#[derive(Args, Default)]
+struct MuteArgs {
+    #[arg(short = 's')]
+    silent: bool,
+    #[arg(alias = "s")]
+    since: Option<String>,
+}
+
+fn main() {}
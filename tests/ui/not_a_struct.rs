@@ -0,0 +1,9 @@
+use tgbot_utils::cmd_arg::Args;
+
+#[derive(Args, Default)]
+enum MuteArgs {
+    #[default]
+    Silent,
+}
+
+fn main() {}
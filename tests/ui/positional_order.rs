@@ -0,0 +1,11 @@
+use tgbot_utils::cmd_arg::Args;
+
+#[derive(Args, Default)]
+struct MuteArgs {
+    #[arg(positional)]
+    duration: Option<String>,
+    #[arg(positional)]
+    user: String,
+}
+
+fn main() {}
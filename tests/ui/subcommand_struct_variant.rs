@@ -0,0 +1,15 @@
+use tgbot_utils::cmd_arg::{Args, Subcommands};
+
+#[derive(Args, Default)]
+struct GetArgs {
+    #[arg(positional)]
+    key: String,
+}
+
+#[derive(Subcommands)]
+enum ConfigCmd {
+    Get(GetArgs),
+    Set { key: String, value: String },
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use tgbot_utils::cmd_arg::Args;
+
+#[derive(Args, Default)]
+struct MuteArgs {
+    #[arg(positional, hidden)]
+    user: String,
+}
+
+fn main() {}
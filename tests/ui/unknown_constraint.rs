@@ -0,0 +1,16 @@
+use tgbot_utils::cmd_arg::Args;
+
+#[derive(Args, Default)]
+struct BanArgs {
+    #[arg(requires = "bann")]
+    until: Option<String>,
+    ban: bool,
+}
+
+#[derive(Args, Default)]
+#[arg(exactly_one_of("ban"))]
+struct MuteArgs {
+    ban: bool,
+}
+
+fn main() {}
@@ -0,0 +1,10 @@
+use tgbot_utils::cmd_arg::Args;
+
+#[derive(Args, Default)]
+struct MuteArgs {
+    users: (String, String),
+    #[arg(positional)]
+    chats: Vec<String>,
+}
+
+fn main() {}